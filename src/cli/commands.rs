@@ -1,4 +1,17 @@
-use clap::Subcommand;
+use clap::{ArgGroup, Args, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::config::Config;
+use crate::engine::color::parse_hex_color;
+use crate::engine::control::Control;
+use crate::engine::{
+    Anchor, Easing, Order, Paint, Placement, Point, ScaleFilter, ScaleMode, SlideshowOptions,
+    SortKey, Transition, TransitionKind, WeightBy,
+};
+use crate::errors::{Result, WallrusError};
+use crate::library::{ColorQuery, Tone};
+use crate::utils::ScanOptions;
 
 #[derive(Subcommand)]
 pub enum Commands {
@@ -15,15 +28,73 @@ pub enum Commands {
         /// Artist username to filter by
         #[arg(long)]
         artist: Option<String>,
+
+        #[command(flatten)]
+        placement: PlacementArgs,
+
+        #[command(flatten)]
+        transition: TransitionArgs,
     },
 
-    /// Start a slideshow of wallpapers
-    Slideshow {
-        /// Interval between wallpaper changes in seconds
-        #[arg(long, default_value_t = 5)]
-        interval: u64,
+    /// Set a wallpaper from a file, pick one from the library, or set a solid color
+    /// (`--color` without an image or --random) or gradient (`--gradient`)
+    #[command(group(
+        ArgGroup::new("filters")
+            .args(["tolerance", "dark", "light"])
+            .multiple(true)
+            .requires("random")
+    ))]
+    Set {
+        /// Image to set as wallpaper
+        #[arg(
+            required_unless_present_any = ["random", "color", "gradient"],
+            conflicts_with_all = ["random", "gradient", "tolerance", "dark", "light"]
+        )]
+        path: Option<PathBuf>,
+
+        /// Pick a random image from the image directory
+        #[arg(long)]
+        random: bool,
+
+        /// Directory (or image) to pick from with --random; repeatable [default: IMAGE_PATH]
+        #[arg(long = "source", requires = "random")]
+        sources: Vec<PathBuf>,
+
+        #[command(flatten)]
+        scan: ScanArgs,
+
+        #[command(flatten)]
+        color: ColorArgs,
+
+        #[command(flatten)]
+        paint: PaintArgs,
+
+        #[command(flatten)]
+        placement: PlacementArgs,
+
+        #[command(flatten)]
+        transition: TransitionArgs,
+
+        /// Only set the wallpaper of this output, by name (e.g. DP-1) or description; KDE
+        /// Plasma and Enlightenment take a screen number (e.g. 0) [not GNOME, Cinnamon, MATE,
+        /// Budgie or LXQt]
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Image for the dark style, with the main image for the light style [GNOME 42 and
+        /// later; other desktops show the main image]
+        #[arg(
+            long,
+            value_name = "PATH",
+            requires = "path",
+            conflicts_with = "output"
+        )]
+        dark_image: Option<PathBuf>,
     },
 
+    /// Start a slideshow of wallpapers
+    Slideshow(SlideshowArgs),
+
     /// Generate a new wallpaper
     Generate {
         /// Width of the generated wallpaper
@@ -33,5 +104,478 @@ pub enum Commands {
         /// Height of the generated wallpaper
         #[arg(long, default_value_t = 1080)]
         height: u32,
+
+        #[command(flatten)]
+        placement: PlacementArgs,
+
+        #[command(flatten)]
+        transition: TransitionArgs,
+    },
+
+    /// Manage the wallpaper library index
+    Library {
+        #[command(subcommand)]
+        command: LibraryCommands,
+    },
+
+    /// List the outputs and their wallpapers [native Wayland only]
+    Outputs {
+        /// Set every output listed in the `[outputs]` table of the config file
+        #[arg(long)]
+        apply: bool,
+
+        #[command(flatten)]
+        placement: PlacementArgs,
+
+        #[command(flatten)]
+        transition: TransitionArgs,
     },
+
+    /// List the wallpaper backends in the order they are tried, and which are available
+    Backends,
+
+    /// Explain which desktop and session were detected, and why, and which backends would
+    /// set the wallpaper
+    Doctor,
+
+    /// Run the Wayland wallpaper daemon that owns the wallpaper surfaces
+    ///
+    /// Started automatically when a wallpaper is set through the native Wayland backend.
+    Daemon {
+        /// Stop the running daemon, removing the wallpaper
+        #[arg(long)]
+        stop: bool,
+    },
+
+    /// Control a running slideshow
+    Ctl {
+        #[command(subcommand)]
+        command: CtlCommands,
+
+        /// Print the raw JSON response
+        #[arg(long, global = true)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LibraryCommands {
+    /// Extract and store the color palette of every image in the given directories
+    Index {
+        /// Directories (or images) to index [default: IMAGE_PATH]
+        sources: Vec<PathBuf>,
+
+        #[command(flatten)]
+        scan: ScanArgs,
+    },
+
+    /// Print the dominant colors and brightness of an image
+    Palette {
+        /// Image to analyze
+        path: PathBuf,
+    },
+
+    /// Rate an image from 0 to 5, used by the weighted slideshow order
+    Rate {
+        /// Image to rate
+        path: PathBuf,
+
+        /// Rating from 0 (never) to 5 (favorite)
+        rating: u8,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Copy)]
+pub enum CtlCommands {
+    /// Skip to the next wallpaper
+    Next,
+    /// Go back to the previous wallpaper
+    Prev,
+    /// Stop changing the wallpaper
+    Pause,
+    /// Continue after a pause
+    Resume,
+    /// Change the interval between wallpapers
+    SetInterval {
+        /// New interval in seconds
+        seconds: u64,
+    },
+    /// Show the current wallpaper and the time to the next change
+    Status,
+    /// Rate the current wallpaper 5/5
+    FavoriteCurrent,
+    /// Reload the config file and rescan the sources
+    Reload,
+    /// Stop the slideshow
+    Stop,
+}
+
+impl CtlCommands {
+    pub fn control(self) -> Control {
+        match self {
+            Self::Next => Control::Next,
+            Self::Prev => Control::Previous,
+            Self::Pause => Control::Pause,
+            Self::Resume => Control::Resume,
+            Self::SetInterval { seconds } => Control::SetInterval { seconds },
+            Self::Status => Control::Status,
+            Self::FavoriteCurrent => Control::FavoriteCurrent,
+            Self::Reload => Control::Reload,
+            Self::Stop => Control::Shutdown,
+        }
+    }
+}
+
+/// Slideshow flags. Unset flags fall back to the `[slideshow]` table of the config file.
+#[derive(Args, Debug, Clone)]
+pub struct SlideshowArgs {
+    /// Directories (or images) to show [default: IMAGE_PATH]
+    sources: Vec<PathBuf>,
+
+    /// Interval between wallpaper changes in seconds [default: 5]
+    #[arg(long)]
+    interval: Option<u64>,
+
+    /// Randomly shift each interval by up to this many seconds either way [default: 0]
+    #[arg(long)]
+    jitter: Option<u64>,
+
+    /// Order in which images are shown [default: sequential]
+    #[arg(long, value_enum)]
+    order: Option<Order>,
+
+    /// Sort key for the sequential order [default: name]
+    #[arg(long, value_enum)]
+    sort: Option<SortKey>,
+
+    /// Number of recently shown images to avoid in the random orders [default: 10]
+    #[arg(long)]
+    no_repeat: Option<usize>,
+
+    /// What the weighted order favors [default: staleness]
+    #[arg(long, value_enum)]
+    weight_by: Option<WeightBy>,
+
+    #[command(flatten)]
+    scan: ScanArgs,
+
+    #[command(flatten)]
+    color: ColorArgs,
+
+    #[command(flatten)]
+    placement: PlacementArgs,
+
+    #[command(flatten)]
+    transition: TransitionArgs,
+}
+
+const DEFAULT_INTERVAL: u64 = 5;
+const DEFAULT_NO_REPEAT: usize = 10;
+
+impl SlideshowArgs {
+    /// Combines the flags with the config file; flags win.
+    pub fn resolve(&self, config: &Config) -> Result<SlideshowOptions> {
+        let file = &config.slideshow;
+
+        let sources = if !self.sources.is_empty() {
+            self.sources.clone()
+        } else if !file.sources.is_empty() {
+            file.sources.clone()
+        } else {
+            vec![PathBuf::from(&config.image_path)]
+        };
+
+        let mut scan = self.scan.options();
+        scan.recursive = self.scan.recursive().unwrap_or(file.recursive);
+        if scan.include.is_empty() {
+            scan.include = file.include.clone();
+        }
+        if scan.exclude.is_empty() {
+            scan.exclude = file.exclude.clone();
+        }
+
+        Ok(SlideshowOptions {
+            sources,
+            scan,
+            interval: Duration::from_secs(
+                self.interval.or(file.interval).unwrap_or(DEFAULT_INTERVAL),
+            ),
+            jitter: Duration::from_secs(self.jitter.or(file.jitter).unwrap_or(0)),
+            query: self.color.query()?,
+            order: self.order.or(file.order).unwrap_or_default(),
+            sort: self.sort.or(file.sort).unwrap_or_default(),
+            no_repeat: self
+                .no_repeat
+                .or(file.no_repeat)
+                .unwrap_or(DEFAULT_NO_REPEAT),
+            weight_by: self.weight_by.or(file.weight_by).unwrap_or_default(),
+            placement: self.placement.resolve(config)?,
+            transition: self.transition.resolve(config)?,
+        })
+    }
+}
+
+/// How the wallpaper is fitted to the screen. Unset flags fall back to the `[display]` table
+/// of the config file.
+#[derive(Args, Debug, Clone)]
+pub struct PlacementArgs {
+    /// How the image is scaled to the screen [default: fill]
+    #[arg(long, value_enum)]
+    mode: Option<ScaleMode>,
+
+    /// Which part of the image stays visible when cropped, or where it sits when smaller
+    /// than the screen [default: center]
+    #[arg(long, value_enum)]
+    anchor: Option<Anchor>,
+
+    /// Color around images that do not cover the screen (e.g. "#1e1e2e") [default: #000000]
+    #[arg(long)]
+    background: Option<String>,
+
+    /// Resampling filter used by the native Wayland backend; faster filters trade
+    /// sharpness for speed [default: lanczos3]
+    #[arg(long, value_enum)]
+    filter: Option<ScaleFilter>,
+
+    /// Lay one image across all monitors instead of repeating it on each [X11, GNOME,
+    /// Cinnamon, MATE, Budgie and XFCE]
+    #[arg(long, overrides_with = "no_span")]
+    span: bool,
+
+    /// Repeat the image on each monitor, even if the config file spans it
+    #[arg(long, overrides_with = "span")]
+    no_span: bool,
+}
+
+impl PlacementArgs {
+    pub fn resolve(&self, config: &Config) -> Result<Placement> {
+        let file = &config.display;
+        let background = match self.background.as_ref().or(file.background.as_ref()) {
+            Some(color) => parse_hex_color(color)?,
+            None => (0, 0, 0),
+        };
+        Ok(Placement {
+            mode: self.mode.or(file.mode).unwrap_or_default(),
+            anchor: self.anchor.or(file.anchor).unwrap_or_default(),
+            background,
+            filter: self.filter.or(file.filter).unwrap_or_default(),
+            span: flag(self.span, self.no_span).or(file.span).unwrap_or(false),
+        })
+    }
+}
+
+/// A gradient to set instead of an image; a solid color comes from `--color`.
+#[derive(Args, Debug, Clone)]
+pub struct PaintArgs {
+    /// Set a linear gradient between two colors (e.g. "#1e1e2e" "#45475a")
+    #[arg(long, num_args = 2, value_names = ["FROM", "TO"], conflicts_with_all = ["random", "color", "dark", "light"])]
+    gradient: Option<Vec<String>>,
+
+    /// Direction of --gradient in degrees: 0 runs left to right, 90 top to bottom
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 90.0,
+        requires = "gradient"
+    )]
+    gradient_angle: f64,
+}
+
+impl PaintArgs {
+    /// The gradient, or the solid `--color` of `color` if no gradient is given.
+    pub fn resolve(&self, color: &ColorArgs) -> Result<Paint> {
+        if let Some(colors) = &self.gradient {
+            return Ok(Paint::Gradient {
+                from: parse_hex_color(&colors[0])?,
+                to: parse_hex_color(&colors[1])?,
+                angle: self.gradient_angle,
+            });
+        }
+        match &color.color {
+            Some(color) => Ok(Paint::Color(parse_hex_color(color)?)),
+            None => Err(WallrusError::Config(
+                "Expected an image, --random, --color or --gradient".into(),
+            )),
+        }
+    }
+}
+
+/// How the native Wayland backend animates a change of wallpaper. Unset flags fall back to
+/// the `[transition]` table of the config file.
+#[derive(Args, Debug, Clone)]
+pub struct TransitionArgs {
+    /// Transition to the new wallpaper [default: none]
+    #[arg(long, value_enum)]
+    transition: Option<TransitionKind>,
+
+    /// Length of the transition in seconds [default: 1]
+    #[arg(long, value_name = "SECONDS")]
+    transition_duration: Option<f64>,
+
+    /// Easing curve of the transition [default: ease-in-out]
+    #[arg(long, value_enum, value_name = "EASING")]
+    transition_easing: Option<Easing>,
+
+    /// Direction of the wipe transition in degrees, 0 is left to right and 90 top to bottom
+    #[arg(long, value_name = "DEGREES")]
+    transition_angle: Option<f64>,
+
+    /// Where the grow transition starts: an anchor name such as "top-left", or "x,y"
+    /// fractions of the screen [default: center]
+    #[arg(long, value_name = "POSITION")]
+    transition_position: Option<Point>,
+}
+
+impl TransitionArgs {
+    pub fn resolve(&self, config: &Config) -> Result<Transition> {
+        let file = &config.transition;
+        let defaults = Transition::default();
+        let duration = self
+            .transition_duration
+            .or(file.duration)
+            .unwrap_or(defaults.duration);
+        if !duration.is_finite() || duration < 0.0 {
+            return Err(WallrusError::Config(format!(
+                "Invalid transition duration: {}",
+                duration
+            )));
+        }
+        Ok(Transition {
+            kind: self.transition.or(file.kind).unwrap_or(defaults.kind),
+            duration,
+            easing: self
+                .transition_easing
+                .or(file.easing)
+                .unwrap_or(defaults.easing),
+            angle: self
+                .transition_angle
+                .or(file.angle)
+                .unwrap_or(defaults.angle),
+            position: self
+                .transition_position
+                .or(file.position)
+                .unwrap_or(defaults.position),
+        })
+    }
+}
+
+/// How image source directories are walked.
+#[derive(Args, Debug, Clone)]
+pub struct ScanArgs {
+    /// Include images in subdirectories
+    #[arg(short, long, overrides_with = "no_recursive")]
+    recursive: bool,
+
+    /// Leave out subdirectories, even if the config file includes them
+    #[arg(long, overrides_with = "recursive")]
+    no_recursive: bool,
+
+    /// Only use images whose path (relative to the source) matches this glob; repeatable
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Skip images whose path (relative to the source) matches this glob; repeatable
+    #[arg(long)]
+    exclude: Vec<String>,
+}
+
+impl ScanArgs {
+    /// `--recursive` or `--no-recursive`, if either was given.
+    fn recursive(&self) -> Option<bool> {
+        flag(self.recursive, self.no_recursive)
+    }
+
+    pub fn options(&self) -> ScanOptions {
+        ScanOptions {
+            recursive: self.recursive,
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+        }
+    }
+}
+
+/// Filters for choosing wallpapers by color.
+#[derive(Args, Debug, Clone)]
+pub struct ColorArgs {
+    /// Only use images with a dominant color close to this one (e.g. "#1e3a5f"). `set
+    /// --color` without an image or --random sets the color itself
+    #[arg(long)]
+    color: Option<String>,
+
+    /// Maximum perceptual distance (delta E) for --color matches
+    #[arg(long, default_value_t = 25.0, requires = "color")]
+    tolerance: f32,
+
+    /// Only use dark images
+    #[arg(long, conflicts_with = "light")]
+    dark: bool,
+
+    /// Only use light images
+    #[arg(long)]
+    light: bool,
+}
+
+impl ColorArgs {
+    pub fn query(&self) -> Result<ColorQuery> {
+        let tone = if self.dark {
+            Some(Tone::Dark)
+        } else if self.light {
+            Some(Tone::Light)
+        } else {
+            None
+        };
+
+        Ok(ColorQuery {
+            color: self.color.as_deref().map(parse_hex_color).transpose()?,
+            tone,
+            tolerance: self.tolerance,
+        })
+    }
+}
+
+/// A switch with a `--no-` form: `Some` when either was given, so it can override the config.
+/// The last one given wins, as each overrides the other.
+fn flag(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        slideshow: SlideshowArgs,
+    }
+
+    fn resolve(args: &[&str], config: &Config) -> SlideshowOptions {
+        Cli::try_parse_from([&["wallrus"], args].concat())
+            .unwrap()
+            .slideshow
+            .resolve(config)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_flags_override_config_switches() {
+        let mut config = Config::default();
+        config.slideshow.recursive = true;
+        config.display.span = Some(true);
+        let options = resolve(&["dir"], &config);
+        assert!(options.scan.recursive);
+        assert!(options.placement.span);
+
+        let options = resolve(&["dir", "--no-recursive", "--no-span"], &config);
+        assert!(!options.scan.recursive);
+        assert!(!options.placement.span);
+
+        let options = resolve(&["dir", "--no-recursive", "-r"], &Config::default());
+        assert!(options.scan.recursive);
+    }
 }
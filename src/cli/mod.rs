@@ -1,5 +1,5 @@
 mod commands;
 mod parser;
 
-pub use commands::Commands;
+pub use commands::{Commands, CtlCommands, LibraryCommands};
 pub use parser::Cli;
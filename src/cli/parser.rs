@@ -6,6 +6,16 @@ use clap::Parser;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Wallpaper backends to try, in order (e.g. `--backend swww,native`); see `wallrus
+    /// backends` [default: every available backend by priority]
+    #[arg(
+        long = "backend",
+        global = true,
+        value_delimiter = ',',
+        value_name = "NAME"
+    )]
+    pub backends: Vec<String>,
 }
 
 impl Cli {
@@ -1,51 +1,171 @@
 // config.rs
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
+use crate::engine::{
+    Anchor, Easing, Order, Point, ScaleFilter, ScaleMode, SortKey, TransitionKind, WeightBy,
+};
 use crate::errors::{Result, WallrusError};
 
+/// Settings are read from this file (if it exists), then overridden by environment variables.
+const CONFIG_FILE: &str = "config.toml";
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
-    pub unsplash_access_key: String,
+    pub unsplash_access_key: Option<String>,
     pub image_path: String,
-    #[serde(default = "default_image_quality")]
     pub image_quality: u8,
-    #[serde(default = "default_slideshow_interval")]
-    pub slideshow_interval: u64,
+    /// Wallpaper backends to try, in order, e.g. `["swww", "native"]`. Empty tries every
+    /// available backend by priority.
+    pub backends: Vec<String>,
+    pub slideshow: SlideshowConfig,
+    pub display: DisplayConfig,
+    pub transition: TransitionConfig,
+    /// What each output shows with `wallrus outputs --apply`, by output name.
+    pub outputs: BTreeMap<String, OutputWallpaper>,
+}
+
+/// Slideshow defaults from the `[slideshow]` table. Command-line flags take precedence.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlideshowConfig {
+    pub sources: Vec<PathBuf>,
+    pub interval: Option<u64>,
+    pub jitter: Option<u64>,
+    pub order: Option<Order>,
+    pub sort: Option<SortKey>,
+    pub no_repeat: Option<usize>,
+    pub weight_by: Option<WeightBy>,
+    pub recursive: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// How wallpapers are fitted to the screen, from the `[display]` table. Command-line flags
+/// take precedence.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub mode: Option<ScaleMode>,
+    pub anchor: Option<Anchor>,
+    /// Background color around images that do not cover the screen, e.g. `"#1e1e2e"`
+    pub background: Option<String>,
+    pub filter: Option<ScaleFilter>,
+    pub span: Option<bool>,
+}
+
+/// Transition defaults from the `[transition]` table. Command-line flags take precedence.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransitionConfig {
+    pub kind: Option<TransitionKind>,
+    /// Seconds
+    pub duration: Option<f64>,
+    pub easing: Option<Easing>,
+    /// Degrees
+    pub angle: Option<f64>,
+    pub position: Option<Point>,
+}
+
+/// An entry of the `[outputs]` table: `"generate"`, or the path of an image or of a directory
+/// to pick a random image from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum OutputWallpaper {
+    Generate,
+    Path(PathBuf),
+}
+
+impl From<String> for OutputWallpaper {
+    fn from(value: String) -> Self {
+        if value == "generate" {
+            Self::Generate
+        } else {
+            Self::Path(PathBuf::from(value))
+        }
+    }
+}
+
+impl From<OutputWallpaper> for String {
+    fn from(value: OutputWallpaper) -> Self {
+        match value {
+            OutputWallpaper::Generate => "generate".into(),
+            OutputWallpaper::Path(path) => path.to_string_lossy().into_owned(),
+        }
+    }
 }
 
 fn default_image_quality() -> u8 {
     80
 }
 
-fn default_slideshow_interval() -> u64 {
-    5
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            unsplash_access_key: None,
+            image_path: String::new(),
+            image_quality: default_image_quality(),
+            backends: Vec::new(),
+            slideshow: SlideshowConfig::default(),
+            display: DisplayConfig::default(),
+            transition: TransitionConfig::default(),
+            outputs: BTreeMap::new(),
+        }
+    }
 }
 
 impl Config {
-    pub fn load() -> Result<Self> {
-        let unsplash_access_key = env::var("UNSPLASH_ACCESS_KEY").map_err(|_| {
-            WallrusError::Config("UNSPLASH_ACCESS_KEY not found in environment".into())
-        })?;
-
-        let image_path = env::var("IMAGE_PATH")
-            .map_err(|_| WallrusError::Config("IMAGE_PATH not found in environment".into()))?;
+    /// Location of the config file: `$WALLRUS_CONFIG`, or `config.toml` in the user config
+    /// directory (e.g. `~/.config/wallrus/config.toml`).
+    pub fn path() -> Option<PathBuf> {
+        env::var_os("WALLRUS_CONFIG")
+            .map(PathBuf::from)
+            .or_else(|| dirs::config_dir().map(|dir| dir.join("wallrus").join(CONFIG_FILE)))
+    }
 
-        let config = Self {
-            unsplash_access_key,
-            image_path,
-            image_quality: default_image_quality(),
-            slideshow_interval: default_slideshow_interval(),
+    pub fn load() -> Result<Self> {
+        let mut config = match Self::path() {
+            Some(path) if path.exists() => Self::from_file(&path)?,
+            _ => Self::default(),
         };
 
+        if let Ok(key) = env::var("UNSPLASH_ACCESS_KEY") {
+            config.unsplash_access_key = Some(key);
+        }
+        if let Ok(image_path) = env::var("IMAGE_PATH") {
+            config.image_path = image_path;
+        }
+
         config.validate()?;
         Ok(config)
     }
 
+    fn from_file(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)?;
+        toml::from_str(&data)
+            .map_err(|e| WallrusError::Config(format!("Failed to parse {:?}: {}", path, e)))
+    }
+
+    /// The Unsplash key is only needed for downloads, so it is checked on use.
+    pub fn unsplash_access_key(&self) -> Result<&str> {
+        match self.unsplash_access_key.as_deref() {
+            None => Err(WallrusError::Config(
+                "UNSPLASH_ACCESS_KEY not found in environment or config file".into(),
+            )),
+            Some("") => Err(WallrusError::Config("Unsplash access key is empty".into())),
+            Some(key) => Ok(key),
+        }
+    }
+
     pub fn validate(&self) -> Result<()> {
-        if self.unsplash_access_key.is_empty() {
-            return Err(WallrusError::Config("Unsplash access key is empty".into()));
+        if self.image_path.is_empty() {
+            return Err(WallrusError::Config(
+                "IMAGE_PATH not found in environment or config file".into(),
+            ));
         }
         if !Path::new(&self.image_path).exists() {
             return Err(WallrusError::Config("Image path does not exist".into()));
@@ -58,3 +178,64 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slideshow_table() {
+        let config: Config = toml::from_str(
+            r#"
+            image_path = "/tmp"
+
+            [slideshow]
+            interval = 300
+            jitter = 30
+            order = "random-no-repeat"
+            sources = ["/srv/wallpapers"]
+            recursive = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.image_quality, 80);
+        assert_eq!(config.slideshow.interval, Some(300));
+        assert_eq!(config.slideshow.order, Some(Order::RandomNoRepeat));
+        assert_eq!(
+            config.slideshow.sources,
+            vec![PathBuf::from("/srv/wallpapers")]
+        );
+        assert!(config.slideshow.recursive);
+        assert!(config.unsplash_access_key.is_none());
+    }
+
+    #[test]
+    fn test_parse_outputs_table() {
+        let config: Config = toml::from_str(
+            r#"
+            [outputs]
+            DP-1 = "/srv/portrait.jpg"
+            "HDMI-A-1" = "generate"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.outputs["DP-1"],
+            OutputWallpaper::Path(PathBuf::from("/srv/portrait.jpg"))
+        );
+        assert_eq!(config.outputs["HDMI-A-1"], OutputWallpaper::Generate);
+    }
+
+    #[test]
+    fn test_unknown_slideshow_key_is_rejected() {
+        let result: std::result::Result<Config, _> = toml::from_str(
+            r#"
+            [slideshow]
+            intervall = 5
+            "#,
+        );
+        assert!(result.is_err());
+    }
+}
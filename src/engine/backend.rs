@@ -0,0 +1,275 @@
+//! Wallpaper backends, and the order in which they are tried.
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use super::{Placement, Transition};
+use crate::errors::{Result, WallrusError};
+
+/// One way of setting the wallpaper, such as a desktop's settings or a wallpaper tool.
+pub trait WallpaperBackend {
+    /// Name used by `--backend` and the `backends` config key
+    fn name(&self) -> &'static str;
+
+    /// Whether the backend can work in this session. Only checked for backends the user
+    /// has not pinned, and when setting a wallpaper only until one is found, as some checks
+    /// run a command or connect to the session.
+    fn is_available(&self) -> bool;
+
+    /// Available backends are tried from the highest priority down.
+    fn priority(&self) -> u32;
+
+    /// Sets `image` on one output, matched by name, or on all of them.
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        transition: &Transition,
+    ) -> Result<()>;
+
+    /// Sets separate images for the light and dark style. Backends without a dark style show
+    /// the light image.
+    fn set_light_dark(
+        &self,
+        light: &Path,
+        _dark: &Path,
+        placement: &Placement,
+        transition: &Transition,
+    ) -> Result<()> {
+        self.set(light, None, placement, transition)
+    }
+
+    /// The image the backend shows at the moment, where it can tell.
+    fn current(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Backends chosen with `--backend` or the config, in the order they are tried.
+static PREFERRED: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Pins the backends to try, in order. An empty list goes back to trying every available
+/// backend by priority.
+pub fn prefer_backends(names: &[String]) -> Result<()> {
+    let known: Vec<_> = backends().iter().map(|backend| backend.name()).collect();
+    if known.is_empty() && !names.is_empty() {
+        return Err(WallrusError::Config(
+            "Backends can only be chosen on Linux".into(),
+        ));
+    }
+    if let Some(unknown) = names.iter().find(|name| !known.contains(&name.as_str())) {
+        return Err(WallrusError::Config(format!(
+            "Unknown backend '{}', expected one of: {}",
+            unknown,
+            known.join(", ")
+        )));
+    }
+    *PREFERRED.write().unwrap() = names.to_vec();
+    Ok(())
+}
+
+fn preferred() -> Vec<String> {
+    PREFERRED.read().unwrap().clone()
+}
+
+/// Puts backends in the order they are tried: the pinned ones in the given order, or all of
+/// them by priority.
+fn order(
+    mut backends: Vec<Box<dyn WallpaperBackend>>,
+    preferred: &[String],
+) -> Vec<Box<dyn WallpaperBackend>> {
+    if preferred.is_empty() {
+        backends.sort_by_key(|backend| std::cmp::Reverse(backend.priority()));
+        return backends;
+    }
+    preferred
+        .iter()
+        .filter_map(|name| {
+            let index = backends.iter().position(|backend| backend.name() == name)?;
+            Some(backends.remove(index))
+        })
+        .collect()
+}
+
+/// The available backends in the order they are tried, checked one at a time as they are
+/// needed. Pinned backends count as available.
+fn available(
+    backends: Vec<Box<dyn WallpaperBackend>>,
+    preferred: &[String],
+) -> impl Iterator<Item = Box<dyn WallpaperBackend>> {
+    let pinned = !preferred.is_empty();
+    order(backends, preferred)
+        .into_iter()
+        .filter(move |backend| pinned || backend.is_available())
+}
+
+/// The backends that would be tried, in order, with whether each one is available. This
+/// checks every backend, which `wallrus backends` and `wallrus doctor` want but setting a
+/// wallpaper does not.
+pub fn list_backends() -> Vec<(Box<dyn WallpaperBackend>, bool)> {
+    let pinned = !preferred().is_empty();
+    order(backends(), &preferred())
+        .into_iter()
+        .map(|backend| {
+            let available = pinned || backend.is_available();
+            (backend, available)
+        })
+        .collect()
+}
+
+/// The backend that sets the next wallpaper, unless it fails.
+#[cfg_attr(not(all(target_os = "linux", feature = "wayland")), allow(dead_code))]
+pub fn first_backend() -> Option<Box<dyn WallpaperBackend>> {
+    available(backends(), &preferred()).next()
+}
+
+/// Sets the wallpaper with the first backend that succeeds.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn set_with_backends(
+    image: &Path,
+    output: Option<&str>,
+    placement: &Placement,
+    transition: &Transition,
+) -> Result<()> {
+    try_backends(&[image], |backend| {
+        backend.set(image, output, placement, transition)
+    })
+}
+
+/// Sets separate images for the light and dark style where the desktop has both, and the
+/// light image elsewhere.
+pub fn set_light_dark_wallpaper(
+    light: &Path,
+    dark: &Path,
+    placement: &Placement,
+    transition: &Transition,
+) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    return try_backends(&[light, dark], |backend| {
+        backend.set_light_dark(light, dark, placement, transition)
+    });
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = dark;
+        super::set_wallpaper(light, placement, transition)
+    }
+}
+
+/// Runs `set` with each available backend in turn until one succeeds.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn try_backends(images: &[&Path], set: impl Fn(&dyn WallpaperBackend) -> Result<()>) -> Result<()> {
+    if let Some(image) = images.iter().find(|image| !image.exists()) {
+        return Err(WallrusError::Config(format!(
+            "Wallpaper file does not exist: {:?}",
+            image
+        )));
+    }
+
+    let mut failures = Vec::new();
+    for backend in available(backends(), &preferred()) {
+        match set(backend.as_ref()) {
+            Ok(()) => return Ok(()),
+            Err(e) => failures.push((backend.name(), e)),
+        }
+    }
+    match failures.len() {
+        0 => Err(WallrusError::Config(
+            "No wallpaper backend is available for this desktop, see `wallrus backends`".into(),
+        )),
+        1 => Err(failures.remove(0).1),
+        _ => Err(WallrusError::Config(format!(
+            "Every wallpaper backend failed. {}",
+            failures
+                .iter()
+                .map(|(name, e)| format!("{}: {}", name, e))
+                .collect::<Vec<_>>()
+                .join("; ")
+        ))),
+    }
+}
+
+/// Error for backends that always set every output.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn all_outputs_only(backend: &str, output: Option<&str>) -> Result<()> {
+    match output {
+        Some(output) => Err(WallrusError::Config(format!(
+            "The {} backend cannot set output {} on its own",
+            backend, output
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn backends() -> Vec<Box<dyn WallpaperBackend>> {
+    super::linux::backends()
+}
+
+/// Other platforms have a single way of setting the wallpaper and no backends to choose from.
+#[cfg(not(target_os = "linux"))]
+fn backends() -> Vec<Box<dyn WallpaperBackend>> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake(&'static str, u32, bool);
+
+    thread_local! {
+        /// Each backend whose availability was checked, in order.
+        static CHECKED: std::cell::RefCell<Vec<&'static str>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    impl WallpaperBackend for Fake {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn is_available(&self) -> bool {
+            CHECKED.with_borrow_mut(|checked| checked.push(self.0));
+            self.2
+        }
+
+        fn priority(&self) -> u32 {
+            self.1
+        }
+
+        fn set(&self, _: &Path, _: Option<&str>, _: &Placement, _: &Transition) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fakes() -> Vec<Box<dyn WallpaperBackend>> {
+        vec![
+            Box::new(Fake("swaybg", 30, true)),
+            Box::new(Fake("native", 100, false)),
+            Box::new(Fake("swww", 40, true)),
+        ]
+    }
+
+    fn names(backends: &[Box<dyn WallpaperBackend>]) -> Vec<&'static str> {
+        backends.iter().map(|backend| backend.name()).collect()
+    }
+
+    #[test]
+    fn test_order_by_priority_or_preference() {
+        assert_eq!(names(&order(fakes(), &[])), ["native", "swww", "swaybg"]);
+        let preferred = ["swaybg".to_string(), "native".to_string()];
+        assert_eq!(names(&order(fakes(), &preferred)), ["swaybg", "native"]);
+    }
+
+    #[test]
+    fn test_availability_is_checked_until_one_is_found() {
+        let first = available(fakes(), &[]).next().unwrap();
+        assert_eq!(first.name(), "swww");
+        assert_eq!(CHECKED.take(), ["native", "swww"]);
+
+        let preferred = ["native".to_string()];
+        let first = available(fakes(), &preferred).next().unwrap();
+        assert_eq!(first.name(), "native");
+        assert!(CHECKED.take().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+//! Color helpers shared by the generators, palette extraction and color filters.
+
+use crate::errors::{Result, WallrusError};
+
+pub type Rgb = (u8, u8, u8);
+
+pub fn hsv_to_rgb(hue: i32, saturation: f32, value: f32) -> (u8, u8, u8) {
+    let c = value * saturation;
+    let x = c * (1.0 - (((hue as f32 / 60.0) % 2.0) - 1.0).abs());
+    let m = value - c;
+    let (r_prime, g_prime, b_prime) = if (0..60).contains(&hue) {
+        (c, x, 0.0)
+    } else if (60..120).contains(&hue) {
+        (x, c, 0.0)
+    } else if (120..180).contains(&hue) {
+        (0.0, c, x)
+    } else if (180..240).contains(&hue) {
+        (0.0, x, c)
+    } else if (240..300).contains(&hue) {
+        (x, 0.0, c)
+    } else {
+        (c, 0.0, x)
+    };
+    (
+        ((r_prime + m) * 255.0).round() as u8,
+        ((g_prime + m) * 255.0).round() as u8,
+        ((b_prime + m) * 255.0).round() as u8,
+    )
+}
+
+/// Parses `#rrggbb`, `rrggbb` or the short `#rgb` form.
+pub fn parse_hex_color(input: &str) -> Result<Rgb> {
+    let hex = input.trim().trim_start_matches('#');
+    let invalid = || WallrusError::Config(format!("Invalid color '{}', expected #rrggbb", input));
+
+    let expanded = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+        6 => hex.to_string(),
+        _ => return Err(invalid()),
+    };
+
+    let value = u32::from_str_radix(&expanded, 16).map_err(|_| invalid())?;
+    Ok((
+        ((value >> 16) & 0xff) as u8,
+        ((value >> 8) & 0xff) as u8,
+        (value & 0xff) as u8,
+    ))
+}
+
+pub fn to_hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+/// CIE L*a*b* coordinates (D65 white point).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+fn srgb_to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(channel: f32) -> u8 {
+    let c = if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    };
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+const WHITE_X: f32 = 0.950_47;
+const WHITE_Y: f32 = 1.0;
+const WHITE_Z: f32 = 1.088_83;
+
+pub fn rgb_to_lab(color: Rgb) -> Lab {
+    let r = srgb_to_linear(color.0);
+    let g = srgb_to_linear(color.1);
+    let b = srgb_to_linear(color.2);
+
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / WHITE_X;
+    let y = (0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b) / WHITE_Y;
+    let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / WHITE_Z;
+
+    let f = |t: f32| {
+        if t > 0.008_856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    Lab {
+        l: 116.0 * fy - 16.0,
+        a: 500.0 * (fx - fy),
+        b: 200.0 * (fy - fz),
+    }
+}
+
+pub fn lab_to_rgb(lab: Lab) -> Rgb {
+    let fy = (lab.l + 16.0) / 116.0;
+    let fx = fy + lab.a / 500.0;
+    let fz = fy - lab.b / 200.0;
+
+    let finv = |t: f32| {
+        if t.powi(3) > 0.008_856 {
+            t.powi(3)
+        } else {
+            (t - 16.0 / 116.0) / 7.787
+        }
+    };
+    let x = finv(fx) * WHITE_X;
+    let y = finv(fy) * WHITE_Y;
+    let z = finv(fz) * WHITE_Z;
+
+    let r = 3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z;
+    let g = -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z;
+    let b = 0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z;
+
+    (linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b))
+}
+
+/// Perceptual distance between two colors (CIE76 delta E).
+pub fn delta_e(a: Lab, b: Lab) -> f32 {
+    ((a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#1e3a5f").unwrap(), (0x1e, 0x3a, 0x5f));
+        assert_eq!(parse_hex_color("FFFFFF").unwrap(), (255, 255, 255));
+        assert_eq!(parse_hex_color("#f00").unwrap(), (255, 0, 0));
+        assert!(parse_hex_color("#12345").is_err());
+        assert!(parse_hex_color("#zzzzzz").is_err());
+    }
+
+    #[test]
+    fn test_lab_round_trip() {
+        for color in [(0, 0, 0), (255, 255, 255), (30, 58, 95), (200, 120, 10)] {
+            let back = lab_to_rgb(rgb_to_lab(color));
+            assert!((back.0 as i32 - color.0 as i32).abs() <= 1);
+            assert!((back.1 as i32 - color.1 as i32).abs() <= 1);
+            assert!((back.2 as i32 - color.2 as i32).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_lab_lightness_extremes() {
+        assert!(rgb_to_lab((0, 0, 0)).l.abs() < 0.5);
+        assert!((rgb_to_lab((255, 255, 255)).l - 100.0).abs() < 0.5);
+    }
+}
@@ -1,9 +1,9 @@
-use std::{fs, path::Path, thread, time::Duration};
+use std::path::{Path, PathBuf};
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    engine,
     errors::{Result, WallrusError},
     utils::generate_unique_filename,
 };
@@ -11,6 +11,7 @@ use crate::{
 use super::generators::{
     generate_gradient_wallpaper, generate_random_plot_wallpaper, generate_random_walk_wallpaper,
 };
+use super::Paint;
 
 #[derive(Debug, Clone)]
 pub struct WallpaperConfig {
@@ -76,28 +77,21 @@ impl WallpaperConfig {
     }
 }
 
-pub fn hsv_to_rgb(hue: i32, saturation: f32, value: f32) -> (u8, u8, u8) {
-    let c = value * saturation;
-    let x = c * (1.0 - (((hue as f32 / 60.0) % 2.0) - 1.0).abs());
-    let m = value - c;
-    let (r_prime, g_prime, b_prime) = if (0..60).contains(&hue) {
-        (c, x, 0.0)
-    } else if (60..120).contains(&hue) {
-        (x, c, 0.0)
-    } else if (120..180).contains(&hue) {
-        (0.0, c, x)
-    } else if (180..240).contains(&hue) {
-        (0.0, x, c)
-    } else if (240..300).contains(&hue) {
-        (x, 0.0, c)
-    } else {
-        (c, 0.0, x)
-    };
-    (
-        ((r_prime + m) * 255.0).round() as u8,
-        ((g_prime + m) * 255.0).round() as u8,
-        ((b_prime + m) * 255.0).round() as u8,
-    )
+/// A monitor as reported by the native Wayland backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputStatus {
+    /// Connector name such as `DP-1`
+    pub name: Option<String>,
+    /// Human-readable description, usually make, model and connector
+    pub description: Option<String>,
+    /// Size of the current mode in pixels
+    pub width: u32,
+    pub height: u32,
+    /// The image shown on the output, if any
+    pub image: Option<PathBuf>,
+    /// The color or gradient shown instead of an image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paint: Option<Paint>,
 }
 
 #[derive(Debug)]
@@ -107,8 +101,9 @@ pub enum WallpaperType {
     RandomPlot,
 }
 
-/// Generates a wallpaper based on the specified type and saves it to the given file path.
-pub fn generate_wallpaper(width: u32, height: u32, file_path: &str) -> Result<()> {
+/// Generates a wallpaper of a random type and saves it in the given directory. Returns the
+/// path of the new image.
+pub fn generate_wallpaper(width: u32, height: u32, file_path: &str) -> Result<PathBuf> {
     let file_path = generate_unique_filename(file_path, "jpg");
     let file_path = Path::new(&file_path);
 
@@ -124,39 +119,5 @@ pub fn generate_wallpaper(width: u32, height: u32, file_path: &str) -> Result<()
         WallpaperType::RandomPlot => generate_random_plot_wallpaper(width, height, file_path)?,
     }
 
-    Ok(())
-}
-
-/// Creates a slideshow from images in a specified directory, changing wallpaper at a given interval.
-pub fn create_slideshow(image_directory: &str, interval: Duration) -> Result<()> {
-    let paths = fs::read_dir(image_directory).map_err(WallrusError::Io)?;
-
-    let valid_paths: Vec<_> = paths
-        .filter_map(|entry| {
-            entry.ok().filter(|e| {
-                let path = e.path();
-                path.extension()
-                    .and_then(|ext| ext.to_str())
-                    .map(|ext| ext == "jpg" || ext == "png")
-                    .unwrap_or(false)
-            })
-        })
-        .collect();
-
-    if valid_paths.is_empty() {
-        return Err(WallrusError::Config(
-            "No valid images found in directory".into(),
-        ));
-    }
-
-    println!("Starting slideshow with {} images", valid_paths.len());
-
-    loop {
-        for entry in &valid_paths {
-            let image_path = entry.path();
-            println!("Setting wallpaper: {:?}", image_path);
-            engine::set_wallpaper(&image_path)?;
-            thread::sleep(interval);
-        }
-    }
+    Ok(file_path.to_path_buf())
 }
@@ -0,0 +1,352 @@
+//! External control of a running slideshow, through process signals and a Unix socket.
+//!
+//! The socket speaks newline-delimited JSON: a client sends one request such as
+//! `{"command":"set-interval","seconds":60}` and receives one response such as
+//! `{"ok":true,"status":{...}}` before the connection is closed.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::errors::{Result, WallrusError};
+
+const SOCKET_FILE: &str = "wallrus.sock";
+
+/// A request to the running slideshow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Control {
+    /// Save the position and exit (SIGTERM, SIGINT)
+    #[serde(rename = "stop")]
+    Shutdown,
+    /// Reload the config file and rescan the sources (SIGHUP)
+    Reload,
+    /// Skip to the next image (SIGUSR1)
+    Next,
+    /// Go back to the previous image (SIGUSR2)
+    #[serde(rename = "prev")]
+    Previous,
+    /// Stop changing the wallpaper until resumed
+    Pause,
+    /// Continue after a pause
+    Resume,
+    /// Change the interval between wallpapers
+    SetInterval { seconds: u64 },
+    /// Only report the status
+    Status,
+    /// Give the current image the highest rating
+    FavoriteCurrent,
+}
+
+/// What the slideshow is doing, sent back for every request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub current: Option<PathBuf>,
+    pub paused: bool,
+    pub images: usize,
+    /// Interval between changes in seconds
+    pub interval: u64,
+    /// Seconds until the next change, if one is scheduled
+    pub next_change: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    pub fn new(result: Result<()>, status: Status) -> Self {
+        Self {
+            ok: result.is_ok(),
+            error: result.err().map(|e| match e {
+                // The client reports the error as its own configuration error.
+                WallrusError::Config(message) => message,
+                e => e.to_string(),
+            }),
+            status: Some(status),
+        }
+    }
+
+    fn error(message: String) -> Self {
+        Self {
+            ok: false,
+            error: Some(message),
+            status: None,
+        }
+    }
+}
+
+/// A control request together with the way to answer it. Signals cannot be answered.
+pub struct Request {
+    pub control: Control,
+    reply: Option<oneshot::Sender<Response>>,
+}
+
+impl Request {
+    pub fn reply(self, response: Response) {
+        if let Some(reply) = self.reply {
+            // The client may have hung up already; nothing to do about that.
+            let _ = reply.send(response);
+        }
+    }
+}
+
+impl From<Control> for Request {
+    fn from(control: Control) -> Self {
+        Self {
+            control,
+            reply: None,
+        }
+    }
+}
+
+/// Location of the control socket: `wallrus.sock` in the runtime directory.
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(crate::utils::runtime_dir()?.join(SOCKET_FILE))
+}
+
+/// Turns process signals into `Control` requests.
+#[cfg(unix)]
+pub struct ControlSignals {
+    terminate: tokio::signal::unix::Signal,
+    interrupt: tokio::signal::unix::Signal,
+    hangup: tokio::signal::unix::Signal,
+    user1: tokio::signal::unix::Signal,
+    user2: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl ControlSignals {
+    pub fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+            hangup: signal(SignalKind::hangup())?,
+            user1: signal(SignalKind::user_defined1())?,
+            user2: signal(SignalKind::user_defined2())?,
+        })
+    }
+
+    /// Waits for the next signal. Cancel-safe, so it can be used in `tokio::select!`.
+    pub async fn recv(&mut self) -> Control {
+        tokio::select! {
+            _ = self.terminate.recv() => Control::Shutdown,
+            _ = self.interrupt.recv() => Control::Shutdown,
+            _ = self.hangup.recv() => Control::Reload,
+            _ = self.user1.recv() => Control::Next,
+            _ = self.user2.recv() => Control::Previous,
+        }
+    }
+}
+
+/// Without Unix signals only Ctrl+C is available.
+#[cfg(not(unix))]
+pub struct ControlSignals;
+
+#[cfg(not(unix))]
+impl ControlSignals {
+    pub fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    pub async fn recv(&mut self) -> Control {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => Control::Shutdown,
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use socket::{send, ControlSocket};
+
+#[cfg(unix)]
+mod socket {
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{UnixListener, UnixStream};
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    use super::{socket_path, Control, Request, Response};
+    use crate::errors::{Result, WallrusError};
+    use crate::utils::claim_socket;
+
+    /// Requests longer than this are rejected.
+    const MAX_REQUEST: u64 = 4096;
+
+    /// Listens on the control socket and hands requests to the slideshow.
+    pub struct ControlSocket {
+        path: PathBuf,
+        requests: UnboundedReceiver<Request>,
+        listener: JoinHandle<()>,
+    }
+
+    impl ControlSocket {
+        /// Binds the socket, replacing a stale one left behind by a crashed slideshow.
+        pub fn bind() -> Result<Self> {
+            let path = socket_path()?;
+            if !claim_socket(&path)? {
+                return Err(WallrusError::Config(format!(
+                    "Another slideshow is already running (socket {:?})",
+                    path
+                )));
+            }
+
+            let listener = UnixListener::bind(&path).map_err(|e| {
+                WallrusError::Config(format!("Failed to bind control socket {:?}: {}", path, e))
+            })?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+            let (tx, requests) = unbounded_channel();
+            Ok(Self {
+                path,
+                requests,
+                listener: tokio::spawn(accept(listener, tx)),
+            })
+        }
+
+        /// Waits for the next request. Cancel-safe, so it can be used in `tokio::select!`.
+        pub async fn recv(&mut self) -> Request {
+            match self.requests.recv().await {
+                Some(request) => request,
+                None => std::future::pending().await,
+            }
+        }
+    }
+
+    impl Drop for ControlSocket {
+        fn drop(&mut self) {
+            self.listener.abort();
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    async fn accept(listener: UnixListener, requests: UnboundedSender<Request>) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, requests.clone()));
+                }
+                Err(e) => eprintln!("Warning: control socket error: {}", e),
+            }
+        }
+    }
+
+    /// Answers a single request on one connection.
+    async fn serve(stream: UnixStream, requests: UnboundedSender<Request>) {
+        let (read, mut write) = stream.into_split();
+        let mut line = String::new();
+        if BufReader::new(read.take(MAX_REQUEST))
+            .read_line(&mut line)
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let response = match serde_json::from_str::<Control>(&line) {
+            Ok(control) => {
+                let (reply, response) = oneshot::channel();
+                let request = Request {
+                    control,
+                    reply: Some(reply),
+                };
+                if requests.send(request).is_err() {
+                    return;
+                }
+                match response.await {
+                    Ok(response) => response,
+                    Err(_) => return,
+                }
+            }
+            Err(e) => Response::error(format!("Invalid request: {}", e)),
+        };
+
+        if let Ok(mut data) = serde_json::to_vec(&response) {
+            data.push(b'\n');
+            let _ = write.write_all(&data).await;
+        }
+    }
+
+    /// Sends one request to the running slideshow and waits for its answer.
+    pub async fn send(control: Control) -> Result<Response> {
+        let path = socket_path()?;
+        let stream = UnixStream::connect(&path)
+            .await
+            .map_err(|e| match e.kind() {
+                ErrorKind::NotFound | ErrorKind::ConnectionRefused => WallrusError::Config(
+                    format!("No slideshow is running (no socket at {:?})", path),
+                ),
+                _ => WallrusError::Io(e),
+            })?;
+        let (read, mut write) = stream.into_split();
+
+        let mut request = serde_json::to_vec(&control)
+            .map_err(|e| WallrusError::Config(format!("Failed to encode request: {}", e)))?;
+        request.push(b'\n');
+        write.write_all(&request).await?;
+
+        let mut line = String::new();
+        BufReader::new(read).read_line(&mut line).await?;
+        serde_json::from_str(&line)
+            .map_err(|e| WallrusError::Config(format!("Invalid response from slideshow: {}", e)))
+    }
+}
+
+/// The control socket needs Unix domain sockets; elsewhere it never receives anything.
+#[cfg(not(unix))]
+pub struct ControlSocket;
+
+#[cfg(not(unix))]
+impl ControlSocket {
+    pub fn bind() -> Result<Self> {
+        Ok(Self)
+    }
+
+    pub async fn recv(&mut self) -> Request {
+        std::future::pending().await
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn send(_control: Control) -> Result<Response> {
+    Err(WallrusError::Config(
+        "Slideshow control is only available on Unix".into(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_format() {
+        assert_eq!(
+            serde_json::from_str::<Control>(r#"{"command":"prev"}"#).unwrap(),
+            Control::Previous
+        );
+        assert_eq!(
+            serde_json::from_str::<Control>(r#"{"command":"set-interval","seconds":60}"#).unwrap(),
+            Control::SetInterval { seconds: 60 }
+        );
+        assert_eq!(
+            serde_json::to_string(&Control::FavoriteCurrent).unwrap(),
+            r#"{"command":"favorite-current"}"#
+        );
+        assert!(serde_json::from_str::<Control>(r#"{"command":"dance"}"#).is_err());
+    }
+}
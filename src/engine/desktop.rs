@@ -0,0 +1,388 @@
+//! Works out which desktop and session type wallrus runs in, and why.
+
+use std::fmt;
+use std::fs;
+use std::sync::OnceLock;
+
+/// Environment variables that detection looks at, in the order `wallrus doctor` shows them.
+pub const VARIABLES: &[&str] = &[
+    "XDG_CURRENT_DESKTOP",
+    "XDG_SESSION_DESKTOP",
+    "DESKTOP_SESSION",
+    "XDG_SESSION_TYPE",
+    "WAYLAND_DISPLAY",
+    "DISPLAY",
+    "SWAYSOCK",
+    "HYPRLAND_INSTANCE_SIGNATURE",
+    "KDE_SESSION_VERSION",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Desktop {
+    /// GNOME, including Unity, GNOME Classic and GNOME Flashback
+    Gnome,
+    Kde,
+    Xfce,
+    Cinnamon,
+    Mate,
+    Lxqt,
+    Budgie,
+    Deepin,
+    Enlightenment,
+    Hyprland,
+    Sway,
+}
+
+impl Desktop {
+    /// Matches a desktop name as found in `XDG_CURRENT_DESKTOP` or a session name such as
+    /// `DESKTOP_SESSION`, ignoring case.
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        let desktop = match name.as_str() {
+            "gnome" | "unity" | "gnome-classic" | "gnome-flashback" | "ubuntu" => Self::Gnome,
+            "kde" | "plasma" | "plasmawayland" | "plasmax11" => Self::Kde,
+            "xfce" | "xfce4" => Self::Xfce,
+            "x-cinnamon" | "cinnamon" | "cinnamon2d" | "cinnamon-wayland" => Self::Cinnamon,
+            "mate" => Self::Mate,
+            "lxqt" => Self::Lxqt,
+            "budgie" | "budgie-desktop" => Self::Budgie,
+            "deepin" | "dde" => Self::Deepin,
+            "enlightenment" => Self::Enlightenment,
+            "hyprland" => Self::Hyprland,
+            "sway" => Self::Sway,
+            _ => return None,
+        };
+        Some(desktop)
+    }
+
+    /// The desktop a running process belongs to, by its name in `/proc/<pid>/comm`.
+    fn from_process(name: &str) -> Option<Self> {
+        let desktop = match name {
+            "gnome-shell" => Self::Gnome,
+            "plasmashell" | "kwin_wayland" | "kwin_x11" => Self::Kde,
+            "xfce4-session" | "xfdesktop" => Self::Xfce,
+            "cinnamon" | "cinnamon-session" => Self::Cinnamon,
+            "mate-session" => Self::Mate,
+            "lxqt-session" => Self::Lxqt,
+            "budgie-wm" | "budgie-panel" => Self::Budgie,
+            "dde-desktop" | "dde-session" => Self::Deepin,
+            "enlightenment" => Self::Enlightenment,
+            "Hyprland" => Self::Hyprland,
+            "sway" => Self::Sway,
+            _ => return None,
+        };
+        Some(desktop)
+    }
+
+    /// Whether the desktop draws its own background, which is then set through its settings
+    /// rather than with a layer surface or a wallpaper tool.
+    #[cfg_attr(
+        not(all(target_os = "linux", any(feature = "wayland", feature = "x11"))),
+        allow(dead_code)
+    )]
+    pub fn draws_own_background(self) -> bool {
+        !matches!(self, Self::Hyprland | Self::Sway)
+    }
+}
+
+impl fmt::Display for Desktop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Gnome => "GNOME",
+            Self::Kde => "KDE Plasma",
+            Self::Xfce => "XFCE",
+            Self::Cinnamon => "Cinnamon",
+            Self::Mate => "MATE",
+            Self::Lxqt => "LXQt",
+            Self::Budgie => "Budgie",
+            Self::Deepin => "Deepin",
+            Self::Enlightenment => "Enlightenment",
+            Self::Hyprland => "Hyprland",
+            Self::Sway => "Sway",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionType {
+    Wayland,
+    X11,
+}
+
+impl fmt::Display for SessionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Wayland => "Wayland",
+            Self::X11 => "X11",
+        })
+    }
+}
+
+/// What was detected, each with the reason it was chosen.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Detection {
+    pub desktop: Option<(Desktop, String)>,
+    pub session: Option<(SessionType, String)>,
+    /// Major version of KDE Plasma, from `KDE_SESSION_VERSION`
+    pub plasma_version: Option<(u32, String)>,
+}
+
+impl Detection {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn desktop(&self) -> Option<Desktop> {
+        self.desktop.as_ref().map(|(desktop, _)| *desktop)
+    }
+
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn is_wayland(&self) -> bool {
+        matches!(self.session, Some((SessionType::Wayland, _)))
+    }
+}
+
+/// Detects the desktop and session type from environment variables, looked up with `var`.
+/// Running processes are only listed when no variable names a known desktop.
+///
+/// The desktop comes from, in order: each entry of the colon-separated
+/// `XDG_CURRENT_DESKTOP` (`ubuntu:GNOME`), `XDG_SESSION_DESKTOP`, `DESKTOP_SESSION` (also a
+/// path to a session file), `HYPRLAND_INSTANCE_SIGNATURE`, `SWAYSOCK`, and finally the
+/// running processes.
+pub fn detect(
+    var: impl Fn(&str) -> Option<String>,
+    processes: impl FnOnce() -> Vec<String>,
+) -> Detection {
+    let set = |name: &str| var(name).filter(|value| !value.is_empty());
+    let explain = |name: &str, value: &str| format!("{}={}", name, value);
+
+    let mut desktop = set("XDG_CURRENT_DESKTOP").and_then(|value| {
+        value
+            .split(':')
+            .find_map(Desktop::from_name)
+            .map(|desktop| (desktop, explain("XDG_CURRENT_DESKTOP", &value)))
+    });
+    for name in ["XDG_SESSION_DESKTOP", "DESKTOP_SESSION"] {
+        if desktop.is_some() {
+            break;
+        }
+        desktop = set(name).and_then(|value| {
+            let session = value.rsplit('/').next().unwrap_or(&value);
+            let session = session.strip_suffix(".desktop").unwrap_or(session);
+            Desktop::from_name(session).map(|desktop| (desktop, explain(name, &value)))
+        });
+    }
+    for (name, compositor) in [
+        ("HYPRLAND_INSTANCE_SIGNATURE", Desktop::Hyprland),
+        ("SWAYSOCK", Desktop::Sway),
+    ] {
+        if desktop.is_none() && set(name).is_some() {
+            desktop = Some((compositor, format!("{} is set", name)));
+        }
+    }
+    if desktop.is_none() {
+        desktop = processes().iter().find_map(|process| {
+            Desktop::from_process(process)
+                .map(|desktop| (desktop, format!("{} is running", process)))
+        });
+    }
+
+    let session = match set("XDG_SESSION_TYPE") {
+        Some(value) if value == "wayland" => Some(SessionType::Wayland),
+        Some(value) if value == "x11" => Some(SessionType::X11),
+        _ => None,
+    }
+    .map(|session| {
+        let value = set("XDG_SESSION_TYPE").unwrap_or_default();
+        (session, explain("XDG_SESSION_TYPE", &value))
+    })
+    .or_else(|| {
+        ["WAYLAND_DISPLAY", "HYPRLAND_INSTANCE_SIGNATURE", "SWAYSOCK"]
+            .into_iter()
+            .find(|name| set(name).is_some())
+            .map(|name| (SessionType::Wayland, format!("{} is set", name)))
+    })
+    .or_else(|| set("DISPLAY").map(|_| (SessionType::X11, "DISPLAY is set".to_string())));
+
+    let plasma_version = set("KDE_SESSION_VERSION").and_then(|value| {
+        let version = value.trim().parse().ok()?;
+        Some((version, explain("KDE_SESSION_VERSION", &value)))
+    });
+
+    Detection {
+        desktop,
+        session,
+        plasma_version,
+    }
+}
+
+/// Detection for this process, done once.
+pub fn detected() -> &'static Detection {
+    static DETECTED: OnceLock<Detection> = OnceLock::new();
+    DETECTED.get_or_init(|| detect(|name| std::env::var(name).ok(), running_processes))
+}
+
+/// A process as far as detection cares: its name, owner and login session.
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq)]
+struct Process {
+    name: String,
+    uid: u32,
+    /// The audit session from `/proc/<pid>/sessionid`, unset outside of a login session
+    session: Option<String>,
+}
+
+#[cfg(unix)]
+impl Process {
+    fn read(dir: &std::path::Path) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let name = fs::read_to_string(dir.join("comm")).ok()?;
+        let session = fs::read_to_string(dir.join("sessionid"))
+            .ok()
+            .map(|session| session.trim().to_string())
+            // (u32)-1 is what the kernel reports for no session.
+            .filter(|session| session != "4294967295");
+        Some(Self {
+            name: name.trim_end().to_string(),
+            uid: fs::metadata(dir).ok()?.uid(),
+            session,
+        })
+    }
+}
+
+/// Names of the processes that share `me`'s user and, when it has one, its login session.
+/// Others, such as the gnome-shell of GDM's greeter, say nothing about this desktop.
+#[cfg(unix)]
+fn ours(processes: impl IntoIterator<Item = Process>, me: &Process) -> Vec<String> {
+    processes
+        .into_iter()
+        .filter(|process| process.uid == me.uid)
+        .filter(|process| me.session.is_none() || process.session == me.session)
+        .map(|process| process.name)
+        .collect()
+}
+
+/// Names of this user's running processes in this session, from `/proc`. Empty where there
+/// is no `/proc`.
+#[cfg(unix)]
+fn running_processes() -> Vec<String> {
+    let (Some(me), Ok(entries)) = (
+        Process::read(std::path::Path::new("/proc/self")),
+        fs::read_dir("/proc"),
+    ) else {
+        return Vec::new();
+    };
+    ours(
+        entries
+            .flatten()
+            .filter_map(|entry| Process::read(&entry.path())),
+        &me,
+    )
+}
+
+#[cfg(not(unix))]
+fn running_processes() -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn detect_with(vars: &[(&str, &str)], processes: &[&str]) -> Detection {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        let processes = processes.iter().map(|name| name.to_string()).collect();
+        detect(
+            |name| vars.get(name).map(|value| value.to_string()),
+            || processes,
+        )
+    }
+
+    #[test]
+    fn test_current_desktop_is_a_colon_list() {
+        for value in ["ubuntu:GNOME", "pop:GNOME", "GNOME-Classic:GNOME", "Unity"] {
+            let detection = detect_with(&[("XDG_CURRENT_DESKTOP", value)], &[]);
+            assert_eq!(detection.desktop(), Some(Desktop::Gnome), "{}", value);
+        }
+        let detection = detect_with(&[("XDG_CURRENT_DESKTOP", "Budgie:GNOME")], &[]);
+        assert_eq!(detection.desktop(), Some(Desktop::Budgie));
+        assert_eq!(
+            detection.desktop.unwrap().1,
+            "XDG_CURRENT_DESKTOP=Budgie:GNOME"
+        );
+    }
+
+    #[test]
+    fn test_session_variables_and_plasma_version() {
+        let detection = detect_with(
+            &[
+                ("DESKTOP_SESSION", "/usr/share/xsessions/plasma"),
+                ("XDG_SESSION_TYPE", "x11"),
+                ("KDE_SESSION_VERSION", "6"),
+            ],
+            &[],
+        );
+        assert_eq!(detection.desktop(), Some(Desktop::Kde));
+        assert_eq!(detection.session.unwrap().0, SessionType::X11);
+        assert_eq!(detection.plasma_version.unwrap().0, 6);
+
+        let detection = detect_with(&[("XDG_SESSION_DESKTOP", "cinnamon")], &[]);
+        assert_eq!(detection.desktop(), Some(Desktop::Cinnamon));
+    }
+
+    #[test]
+    fn test_compositor_sockets_imply_wayland() {
+        let detection = detect_with(&[("SWAYSOCK", "/run/user/1000/sway-ipc.sock")], &[]);
+        assert_eq!(detection.desktop(), Some(Desktop::Sway));
+        assert!(detection.is_wayland());
+
+        let detection = detect_with(
+            &[("HYPRLAND_INSTANCE_SIGNATURE", "abc"), ("DISPLAY", ":0")],
+            &[],
+        );
+        assert_eq!(detection.desktop(), Some(Desktop::Hyprland));
+        assert_eq!(
+            detection.session,
+            Some((
+                SessionType::Wayland,
+                "HYPRLAND_INSTANCE_SIGNATURE is set".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_processes_are_the_last_resort() {
+        let detection = detect_with(&[("DISPLAY", ":0")], &["systemd", "xfdesktop"]);
+        assert_eq!(
+            detection.desktop,
+            Some((Desktop::Xfce, "xfdesktop is running".into()))
+        );
+        assert_eq!(detection.session.unwrap().0, SessionType::X11);
+
+        let detection = detect_with(&[("XDG_CURRENT_DESKTOP", "KDE")], &["gnome-shell"]);
+        assert_eq!(detection.desktop(), Some(Desktop::Kde));
+
+        assert_eq!(detect_with(&[], &["bash"]), Detection::default());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_processes_of_this_session_count() {
+        let process = |name: &str, uid, session: Option<&str>| Process {
+            name: name.into(),
+            uid,
+            session: session.map(str::to_string),
+        };
+        let me = process("wallrus", 1000, Some("3"));
+        let processes = [
+            process("gnome-shell", 120, Some("1")),
+            process("gnome-shell", 1000, Some("2")),
+            process("i3", 1000, Some("3")),
+        ];
+        assert_eq!(ours(processes.clone(), &me), ["i3"]);
+        assert_eq!(
+            ours(processes, &process("wallrus", 1000, None)),
+            ["gnome-shell", "i3"]
+        );
+    }
+}
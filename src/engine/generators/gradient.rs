@@ -1,5 +1,4 @@
-use super::super::set_wallpaper;
-use crate::engine::common::hsv_to_rgb;
+use crate::engine::color::hsv_to_rgb;
 use crate::errors::{Result, WallrusError};
 use image::{ImageBuffer, Rgba, RgbaImage};
 use rand::Rng;
@@ -28,6 +27,5 @@ pub fn generate_gradient_wallpaper(width: u32, height: u32, file_path: &Path) ->
     })?;
 
     println!("Gradient wallpaper generated at {:?}", file_path);
-    set_wallpaper(file_path)?;
     Ok(())
 }
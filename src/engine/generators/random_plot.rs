@@ -1,5 +1,4 @@
-use super::super::set_wallpaper;
-use crate::engine::common::hsv_to_rgb;
+use crate::engine::color::hsv_to_rgb;
 use crate::errors::{Result, WallrusError};
 use plotters::prelude::*;
 use rand::Rng;
@@ -55,6 +54,5 @@ pub fn generate_random_plot_wallpaper(width: u32, height: u32, file_path: &Path)
         .map_err(|e| WallrusError::ImageProcessing(format!("Failed to save plot: {}", e)))?;
 
     println!("Random plot wallpaper generated at {:?}", file_path);
-    set_wallpaper(file_path)?;
     Ok(())
 }
@@ -1,5 +1,5 @@
-use super::super::set_wallpaper;
-use crate::engine::common::{hsv_to_rgb, WallpaperConfig};
+use crate::engine::color::hsv_to_rgb;
+use crate::engine::common::WallpaperConfig;
 use crate::errors::{Result, WallrusError};
 use image::{ImageBuffer, Rgba, RgbaImage};
 use indicatif::{ProgressBar, ProgressStyle};
@@ -198,7 +198,7 @@ pub fn generate_random_walk_wallpaper(width: u32, height: u32, file_path: &Path)
 
                 // Update progress
                 progress_counter += 1;
-                if progress_counter % update_frequency == 0 {
+                if progress_counter.is_multiple_of(update_frequency) {
                     pb.set_position(progress_counter);
                 }
             }
@@ -218,7 +218,6 @@ pub fn generate_random_walk_wallpaper(width: u32, height: u32, file_path: &Path)
         .map_err(|e| WallrusError::ImageProcessing(format!("Failed to save image: {}", e)))?;
 
     println!("Random walk wallpaper generated at {:?}", file_path);
-    set_wallpaper(file_path)?;
     Ok(())
 }
 
@@ -0,0 +1,223 @@
+//! GSettings through dconf, without the `gsettings` binary: keys are written with the dconf
+//! service's `Change` method over D-Bus and read back from the user database, a GVDB file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use zbus::blocking::{Connection, Proxy};
+use zvariant::{serialized::Context, Value, LE};
+
+use super::bus_error;
+use crate::errors::{Result, WallrusError};
+
+const SERVICE: &str = "ca.desrt.dconf";
+const WRITER_PATH: &str = "/ca/desrt/dconf/Writer/user";
+const WRITER_INTERFACE: &str = "ca.desrt.dconf.Writer";
+
+/// GVariant context for dconf changesets. zvariant deprecates its GVariant support in favour
+/// of a separate crate, but it is complete for the `a{smv}` dconf needs.
+#[allow(deprecated)]
+fn gvariant() -> Context {
+    Context::new_gvariant(LE, 0)
+}
+
+/// Writes `values` to the keys of the settings directory `dir` (such as
+/// `/org/gnome/desktop/background/`) in a single change.
+pub fn write(bus: &Connection, dir: &str, values: &[(&str, Value<'_>)]) -> Result<()> {
+    // A dconf changeset is an `a{smv}` GVariant: each full key path with its new value, or
+    // nothing to reset it.
+    let changes: HashMap<String, Option<&Value<'_>>> = values
+        .iter()
+        .map(|(key, value)| (format!("{}{}", dir, key), Some(value)))
+        .collect();
+    let blob = zvariant::to_bytes(gvariant(), &changes).map_err(|e| WallrusError::Backend {
+        command: "dconf".into(),
+        message: format!("Failed to encode the change: {}", e),
+    })?;
+
+    let writer = Proxy::new(bus, SERVICE, WRITER_PATH, WRITER_INTERFACE).map_err(bus_error)?;
+    let _tag: String = writer.call("Change", &(blob.bytes(),)).map_err(bus_error)?;
+    Ok(())
+}
+
+/// Reads a string key from the user database, `None` if it is not set there or the database
+/// cannot be read.
+pub fn read_string(dir: &str, key: &str) -> Option<String> {
+    let database = fs::read(database_path()?).ok()?;
+    lookup_string(&database, &format!("{}{}", dir, key))
+}
+
+/// The user database, `~/.config/dconf/user`.
+fn database_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("dconf").join("user"))
+}
+
+/// Size of each item in a GVDB hash table.
+const GVDB_ITEM: usize = 24;
+
+/// Finds `key` in a GVDB file and decodes its value as a string.
+///
+/// The root hash table lists every item with its key relative to a parent item, so keys
+/// are rebuilt by following parents; the bloom filter and buckets are skipped.
+fn lookup_string(db: &[u8], key: &str) -> Option<String> {
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            db.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    if db.get(..8)? != b"GVariant" {
+        return None;
+    }
+    let (root_start, root_end) = (u32_at(16)? as usize, u32_at(20)? as usize);
+    let bloom_words = (u32_at(root_start)? & ((1 << 27) - 1)) as usize;
+    let buckets = u32_at(root_start + 4)? as usize;
+    let items_start = root_start + 8 + 4 * (bloom_words + buckets);
+    let items = root_end.checked_sub(items_start)? / GVDB_ITEM;
+
+    let item = |index: usize| {
+        let offset = items_start + index * GVDB_ITEM;
+        let parent = u32_at(offset + 4)?;
+        let key_start = u32_at(offset + 8)? as usize;
+        let key_size = u16::from_le_bytes(db.get(offset + 12..offset + 14)?.try_into().ok()?);
+        let segment = db.get(key_start..key_start + key_size as usize)?;
+        let kind = *db.get(offset + 14)?;
+        let value = (u32_at(offset + 16)? as usize, u32_at(offset + 20)? as usize);
+        Some((parent, segment, kind, value))
+    };
+    let full_key = |mut index: usize| {
+        let mut segments = Vec::new();
+        // Parents come before their children, which bounds the walk.
+        for _ in 0..=items {
+            let (parent, segment, _, _) = item(index)?;
+            segments.push(segment);
+            if parent == u32::MAX {
+                segments.reverse();
+                return Some(segments.concat());
+            }
+            index = parent as usize;
+        }
+        None
+    };
+
+    let (_, _, kind, (start, end)) = (0..items)
+        .filter(|&index| full_key(index).as_deref() == Some(key.as_bytes()))
+        .find_map(item)?;
+    if kind != b'v' {
+        return None;
+    }
+    // A serialized variant is the child value, a zero byte and the child's type.
+    let variant = db.get(start..end)?;
+    let separator = variant.iter().rposition(|&byte| byte == 0)?;
+    if &variant[separator + 1..] != b"s" {
+        return None;
+    }
+    let string = variant[..separator].strip_suffix(&[0])?;
+    String::from_utf8(string.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::linux::test_bus::TestBus;
+    use std::sync::{Arc, Mutex};
+
+    const GVDB_HEADER: usize = 24;
+
+    /// Stands in for dconf-service and records every changeset it receives.
+    struct Writer(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    #[zbus::interface(name = "ca.desrt.dconf.Writer")]
+    impl Writer {
+        fn change(&self, blob: Vec<u8>) -> String {
+            let mut changes = self.0.lock().unwrap();
+            changes.push(blob);
+            format!("change-{}", changes.len())
+        }
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn test_write_sends_a_changeset() {
+        let bus = TestBus::start();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let _service = bus.serve(SERVICE, WRITER_PATH, Writer(received.clone()));
+
+        write(
+            &bus.connect(),
+            "/org/gnome/desktop/background/",
+            &[
+                ("picture-uri", Value::from("file:///tmp/a.png")),
+                ("picture-options", Value::from("zoom")),
+            ],
+        )
+        .unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let blob = zvariant::serialized::Data::new(received[0].as_slice(), gvariant());
+        let (changes, _): (HashMap<String, Option<Value>>, _) = blob.deserialize().unwrap();
+        assert_eq!(
+            changes["/org/gnome/desktop/background/picture-uri"],
+            Some(Value::from("file:///tmp/a.png"))
+        );
+        assert_eq!(changes.len(), 2);
+    }
+
+    /// Builds a GVDB file with a flat list of items: (parent, key segment, string value).
+    fn gvdb(items: &[(u32, &str, Option<&str>)]) -> Vec<u8> {
+        let items_start = GVDB_HEADER + 8;
+        let mut data = Vec::new();
+        let mut table = Vec::new();
+        let data_start = items_start + items.len() * GVDB_ITEM;
+        for &(parent, segment, value) in items {
+            let key_start = data_start + data.len();
+            data.extend_from_slice(segment.as_bytes());
+            let (kind, start, end) = match value {
+                Some(value) => {
+                    let start = data_start + data.len();
+                    data.extend_from_slice(value.as_bytes());
+                    data.extend_from_slice(b"\0\0s");
+                    (b'v', start, data_start + data.len())
+                }
+                None => (b'L', 0, 0),
+            };
+            table.extend_from_slice(&0u32.to_le_bytes());
+            table.extend_from_slice(&parent.to_le_bytes());
+            table.extend_from_slice(&(key_start as u32).to_le_bytes());
+            table.extend_from_slice(&(segment.len() as u16).to_le_bytes());
+            table.extend_from_slice(&[kind, 0]);
+            table.extend_from_slice(&(start as u32).to_le_bytes());
+            table.extend_from_slice(&(end as u32).to_le_bytes());
+        }
+        let mut file = b"GVariant".to_vec();
+        file.extend_from_slice(&0u32.to_le_bytes());
+        file.extend_from_slice(&0u32.to_le_bytes());
+        file.extend_from_slice(&(GVDB_HEADER as u32).to_le_bytes());
+        file.extend_from_slice(&(data_start as u32).to_le_bytes());
+        file.extend_from_slice(&[0; 8]);
+        file.extend(table);
+        file.extend(data);
+        file
+    }
+
+    #[test]
+    fn test_lookup_follows_parents() {
+        let db = gvdb(&[
+            (u32::MAX, "/", None),
+            (0, "org/gnome/desktop/background/", None),
+            (1, "picture-uri", Some("file:///tmp/a.png")),
+            (1, "picture-options", Some("zoom")),
+        ]);
+        assert_eq!(
+            lookup_string(&db, "/org/gnome/desktop/background/picture-uri").as_deref(),
+            Some("file:///tmp/a.png")
+        );
+        assert_eq!(
+            lookup_string(&db, "/org/gnome/desktop/background/picture-options").as_deref(),
+            Some("zoom")
+        );
+        assert_eq!(lookup_string(&db, "/org/gnome/desktop/background/"), None);
+        assert_eq!(lookup_string(b"not a database", "/org/"), None);
+    }
+}
@@ -0,0 +1,301 @@
+//! Deepin (DDE), through the Appearance and Display services of dde-daemon.
+
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{Connection, Proxy};
+use zbus::names::BusName;
+use zvariant::OwnedObjectPath;
+
+use super::{bus_error, desktop_is, generated_path, session_bus};
+use crate::engine::backend::WallpaperBackend;
+use crate::engine::desktop::Desktop;
+use crate::engine::{scaling, Placement, ScaleMode, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::open_image;
+
+/// Images rendered for Deepin go here, under the state directory.
+const RENDER_DIR: &str = "deepin";
+
+/// The dde-daemon services of one DDE release.
+struct Api {
+    appearance: &'static str,
+    appearance_path: &'static str,
+    display: &'static str,
+    display_path: &'static str,
+    monitor: &'static str,
+}
+
+/// DDE 23 renamed every service; DDE 20 and earlier use the `com.deepin` names.
+const APIS: [Api; 2] = [
+    Api {
+        appearance: "org.deepin.dde.Appearance1",
+        appearance_path: "/org/deepin/dde/Appearance1",
+        display: "org.deepin.dde.Display1",
+        display_path: "/org/deepin/dde/Display1",
+        monitor: "org.deepin.dde.Display1.Monitor",
+    },
+    Api {
+        appearance: "com.deepin.daemon.Appearance",
+        appearance_path: "/com/deepin/daemon/Appearance",
+        display: "com.deepin.daemon.Display",
+        display_path: "/com/deepin/daemon/Display",
+        monitor: "com.deepin.daemon.Display.Monitor",
+    },
+];
+
+pub struct Deepin;
+
+/// An enabled monitor with its size.
+struct Monitor {
+    name: String,
+    width: u32,
+    height: u32,
+}
+
+/// The services of the running dde-daemon.
+fn api(bus: &Connection) -> Result<&'static Api> {
+    let dbus = DBusProxy::new(bus).map_err(bus_error)?;
+    APIS.iter()
+        .find(|api| {
+            BusName::try_from(api.appearance)
+                .ok()
+                .and_then(|name| dbus.name_has_owner(name).ok())
+                .unwrap_or(false)
+        })
+        .ok_or_else(|| WallrusError::Backend {
+            command: "dde-daemon".into(),
+            message: "the Appearance service is not running".into(),
+        })
+}
+
+fn monitors(bus: &Connection, api: &Api) -> Result<Vec<Monitor>> {
+    let display = Proxy::new(bus, api.display, api.display_path, api.display).map_err(bus_error)?;
+    let paths: Vec<OwnedObjectPath> = display.get_property("Monitors").map_err(bus_error)?;
+    let mut monitors = Vec::new();
+    for path in paths {
+        let monitor = Proxy::new(bus, api.display, path, api.monitor).map_err(bus_error)?;
+        if !monitor.get_property::<bool>("Enabled").map_err(bus_error)? {
+            continue;
+        }
+        monitors.push(Monitor {
+            name: monitor.get_property("Name").map_err(bus_error)?,
+            width: monitor
+                .get_property::<u16>("Width")
+                .map_err(bus_error)?
+                .into(),
+            height: monitor
+                .get_property::<u16>("Height")
+                .map_err(bus_error)?
+                .into(),
+        });
+    }
+    Ok(monitors)
+}
+
+/// Deepin always zooms the image to cover the monitor, so other modes get an image rendered
+/// at the monitor's size.
+fn image_for(image: &Path, monitor: &Monitor, placement: &Placement) -> Result<PathBuf> {
+    if placement.mode == ScaleMode::Fill {
+        return Ok(image.to_path_buf());
+    }
+    let key = (
+        image,
+        monitor.width,
+        monitor.height,
+        format!("{:?}", placement),
+    );
+    let path = generated_path(RENDER_DIR, &monitor.name, key, "png")?;
+
+    let source = open_image(image)?.to_rgba8();
+    scaling::render(&source, monitor.width, monitor.height, placement)
+        .save(&path)
+        .map_err(|e| WallrusError::ImageProcessing(format!("Failed to save {:?}: {}", path, e)))?;
+    Ok(path)
+}
+
+/// Sets `image` on every enabled monitor, or on the one named `output`. dde-daemon gets
+/// an absolute path, as it would resolve a relative one in its own working directory.
+fn set_image(
+    bus: &Connection,
+    image: &Path,
+    output: Option<&str>,
+    placement: &Placement,
+) -> Result<()> {
+    let image = &image.canonicalize()?;
+    let api = api(bus)?;
+    let monitors = monitors(bus, api)?;
+    let targets: Vec<_> = monitors
+        .iter()
+        .filter(|monitor| output.is_none_or(|output| monitor.name == output))
+        .collect();
+    if targets.is_empty() {
+        let names: Vec<_> = monitors
+            .iter()
+            .map(|monitor| monitor.name.as_str())
+            .collect();
+        return Err(WallrusError::Config(format!(
+            "Deepin has no enabled monitor {}, only: {}",
+            output.unwrap_or_default(),
+            names.join(", ")
+        )));
+    }
+
+    let appearance =
+        Proxy::new(bus, api.appearance, api.appearance_path, api.appearance).map_err(bus_error)?;
+    for monitor in targets {
+        let file = image_for(image, monitor, placement)?;
+        appearance
+            .call::<_, _, ()>(
+                "SetMonitorBackground",
+                &(monitor.name.as_str(), file.to_string_lossy().as_ref()),
+            )
+            .map_err(|e| WallrusError::Backend {
+                command: "dde-daemon".into(),
+                message: e.to_string(),
+            })?;
+    }
+    Ok(())
+}
+
+impl WallpaperBackend for Deepin {
+    fn name(&self) -> &'static str {
+        "deepin"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Deepin)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        set_image(&session_bus()?, image, output, placement)
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        let bus = session_bus().ok()?;
+        let api = api(&bus).ok()?;
+        let monitor = monitors(&bus, api).ok()?.into_iter().next()?;
+        let appearance =
+            Proxy::new(&bus, api.appearance, api.appearance_path, api.appearance).ok()?;
+        let uri: String = appearance
+            .call("GetCurrentWorkspaceBackgroundForMonitor", &(monitor.name,))
+            .ok()?;
+        // Older releases answer with a plain path, newer ones with a `file://` URI.
+        match Url::parse(&uri) {
+            Ok(url) => url.to_file_path().ok(),
+            Err(_) => Some(PathBuf::from(uri)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::linux::test_bus::TestBus;
+    use std::sync::{Arc, Mutex};
+
+    /// Stands in for the DDE 20 Appearance service and records each background it is given.
+    struct Appearance(Arc<Mutex<Vec<(String, String)>>>);
+
+    #[zbus::interface(name = "com.deepin.daemon.Appearance")]
+    impl Appearance {
+        fn set_monitor_background(&self, monitor: String, file: String) {
+            self.0.lock().unwrap().push((monitor, file));
+        }
+    }
+
+    struct Display(Vec<OwnedObjectPath>);
+
+    #[zbus::interface(name = "com.deepin.daemon.Display")]
+    impl Display {
+        #[zbus(property)]
+        fn monitors(&self) -> Vec<OwnedObjectPath> {
+            self.0.clone()
+        }
+    }
+
+    struct FakeMonitor(&'static str, bool);
+
+    #[zbus::interface(name = "com.deepin.daemon.Display.Monitor")]
+    impl FakeMonitor {
+        #[zbus(property)]
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+
+        #[zbus(property)]
+        fn enabled(&self) -> bool {
+            self.1
+        }
+
+        #[zbus(property)]
+        fn width(&self) -> u16 {
+            8
+        }
+
+        #[zbus(property)]
+        fn height(&self) -> u16 {
+            4
+        }
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn test_set_each_enabled_monitor() {
+        let bus = TestBus::start();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let api = &APIS[1];
+        let _appearance = bus.serve(
+            api.appearance,
+            api.appearance_path,
+            Appearance(received.clone()),
+        );
+        let monitors = [
+            "/com/deepin/daemon/Display/Monitor_1",
+            "/com/deepin/daemon/Display/Monitor_2",
+        ];
+        let _display = bus
+            .builder()
+            .name(api.display)
+            .unwrap()
+            .serve_at(
+                api.display_path,
+                Display(
+                    monitors
+                        .iter()
+                        .map(|path| OwnedObjectPath::try_from(*path).unwrap())
+                        .collect(),
+                ),
+            )
+            .unwrap()
+            .serve_at(monitors[0], FakeMonitor("HDMI-1", true))
+            .unwrap()
+            .serve_at(monitors[1], FakeMonitor("VGA-1", false))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("a.png");
+        image::RgbaImage::new(2, 2).save(&image).unwrap();
+        let image = image.canonicalize().unwrap();
+        let connection = bus.connect();
+        set_image(&connection, &image, None, &Placement::default()).unwrap();
+        assert_eq!(
+            *received.lock().unwrap(),
+            [("HDMI-1".to_string(), image.to_string_lossy().into_owned())]
+        );
+        assert!(set_image(&connection, &image, Some("VGA-1"), &Placement::default()).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//! Enlightenment, which only shows Edje files: the image is wrapped in one with `edje_cc`
+//! and handed over with `enlightenment_remote`.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use super::{desktop_is, generated_path};
+use crate::engine::backend::WallpaperBackend;
+use crate::engine::desktop::Desktop;
+use crate::engine::{Placement, ScaleMode, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::run;
+
+/// Generated Edje sources and files go here, under the state directory.
+const EDJE_DIR: &str = "enlightenment";
+
+pub struct Enlightenment;
+
+/// A string literal for Edje's C-like source.
+fn edc_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// An Edje background group that shows `image` (found through `edje_cc -id`) with its size,
+/// laid out like Enlightenment's own import dialog does for each mode.
+fn edc(image: &str, width: u32, height: u32, placement: &Placement) -> String {
+    let aspect = width as f64 / height as f64;
+    let layout = match placement.mode {
+        ScaleMode::Fill => format!("aspect: {0} {0}; aspect_preference: NONE;", aspect),
+        ScaleMode::Fit => format!("aspect: {0} {0}; aspect_preference: BOTH;", aspect),
+        ScaleMode::Center => format!("min: {0} {1}; max: {0} {1};", width, height),
+        ScaleMode::Tile => format!(
+            "fill {{ size {{ relative: 0.0 0.0; offset: {} {}; }} }}",
+            width, height
+        ),
+        ScaleMode::Stretch => String::new(),
+    };
+    let (x, y) = placement.anchor.position();
+    let color = placement.background;
+    let image = edc_string(image);
+    format!(
+        "images {{ image: {image} COMP; }}
+collections {{
+    group {{
+        name: \"e/desktop/background\";
+        data {{ item: \"noanimation\" \"1\"; }}
+        parts {{
+            part {{
+                name: \"background\";
+                type: RECT;
+                mouse_events: 0;
+                description {{ state: \"default\" 0.0; color: {r} {g} {b} 255; }}
+            }}
+            part {{
+                name: \"image\";
+                mouse_events: 0;
+                description {{
+                    state: \"default\" 0.0;
+                    align: {x} {y};
+                    {layout}
+                    image {{ normal: {image}; scale_hint: STATIC; }}
+                }}
+            }}
+        }}
+    }}
+}}
+",
+        r = color.0,
+        g = color.1,
+        b = color.2,
+    )
+}
+
+/// Enlightenment numbers its zones (screens) from 0; `--output` takes such a number.
+fn zone(output: Option<&str>) -> Result<String> {
+    match output {
+        None => Ok("-1".into()),
+        Some(output) => output
+            .parse::<u32>()
+            .map(|zone| zone.to_string())
+            .map_err(|_| {
+                WallrusError::Config(format!(
+                    "The enlightenment backend takes a zone number for --output, such as 0, \
+                     not {:?}",
+                    output
+                ))
+            }),
+    }
+}
+
+impl WallpaperBackend for Enlightenment {
+    fn name(&self) -> &'static str {
+        "enlightenment"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Enlightenment)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        let zone = zone(output)?;
+        let image = &image.canonicalize()?;
+        let (dir, file) = match (image.parent(), image.file_name().and_then(|f| f.to_str())) {
+            (Some(dir), Some(file)) => (dir, file),
+            _ => {
+                return Err(WallrusError::Config(format!(
+                    "Enlightenment cannot take the wallpaper path {:?}",
+                    image
+                )))
+            }
+        };
+        let (width, height) = image::io::Reader::open(image)?
+            .with_guessed_format()?
+            .into_dimensions()
+            .map_err(|e| {
+                WallrusError::ImageProcessing(format!("Failed to read {:?}: {}", image, e))
+            })?;
+
+        let stem = image
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("wallpaper");
+        let key = (image, format!("{:?}", placement));
+        let source = generated_path(EDJE_DIR, stem, &key, "edc")?;
+        let edje = source.with_extension("edj");
+        fs::write(&source, edc(file, width, height, placement))?;
+        run(Command::new("edje_cc")
+            .arg("-id")
+            .arg(dir)
+            .arg(&source)
+            .arg(&edje))?;
+
+        // Replace the background of every desk in the zone, or in every zone.
+        let desks = ["0", zone.as_str(), "-1", "-1"];
+        run(Command::new("enlightenment_remote")
+            .arg("-desktop-bg-del")
+            .args(desks))?;
+        run(Command::new("enlightenment_remote")
+            .arg("-desktop-bg-add")
+            .args(desks)
+            .arg(&edje))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Anchor;
+
+    #[test]
+    fn test_edc_lays_out_each_mode() {
+        let placement = |mode| Placement {
+            mode,
+            anchor: Anchor::TopLeft,
+            ..Default::default()
+        };
+        let fit = edc("it's \"a\".png", 400, 200, &placement(ScaleMode::Fit));
+        assert!(fit.contains("images { image: \"it's \\\"a\\\".png\" COMP; }"));
+        assert!(fit.contains("aspect: 2 2; aspect_preference: BOTH;"));
+        assert!(fit.contains("align: 0 0;"));
+        let center = edc("a.png", 400, 200, &placement(ScaleMode::Center));
+        assert!(center.contains("min: 400 200; max: 400 200;"));
+        assert!(zone(Some("eDP-1")).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! GNOME and the desktops that keep their background in GSettings like it: Budgie (with
+//! GNOME's own keys), Cinnamon and MATE.
+
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+use zvariant::Value;
+
+use super::{dconf, desktop_is, session_bus};
+use crate::engine::backend::{all_outputs_only, WallpaperBackend};
+use crate::engine::color::to_hex;
+use crate::engine::desktop::Desktop;
+use crate::engine::{Placement, ScaleMode, Transition};
+use crate::errors::{Result, WallrusError};
+
+/// Where dconf keeps the `org.gnome.desktop.background` schema.
+const SETTINGS: &str = "/org/gnome/desktop/background/";
+
+/// Where dconf keeps the `org.cinnamon.desktop.background` schema.
+const CINNAMON_SETTINGS: &str = "/org/cinnamon/desktop/background/";
+
+/// Where dconf keeps the `org.mate.background` schema.
+const MATE_SETTINGS: &str = "/org/mate/desktop/background/";
+
+pub struct Gnome;
+
+pub struct Budgie;
+
+pub struct Cinnamon;
+
+pub struct Mate;
+
+/// The `picture-options` value for a placement, the same in all three schemas.
+fn picture_options(placement: &Placement) -> &'static str {
+    if placement.span {
+        return "spanned";
+    }
+    match placement.mode {
+        ScaleMode::Fill => "zoom",
+        ScaleMode::Fit => "scaled",
+        ScaleMode::Center => "centered",
+        ScaleMode::Tile => "wallpaper",
+        ScaleMode::Stretch => "stretched",
+    }
+}
+
+/// The percent-encoded `file://` URI of the absolute path of `image`.
+fn uri(image: &Path) -> Result<String> {
+    Url::from_file_path(image.canonicalize()?)
+        .map(String::from)
+        .map_err(|_| WallrusError::Config(format!("Invalid image path: {:?}", image)))
+}
+
+/// The path of a `file://` URI read from the settings.
+fn path(uri: &str) -> Option<PathBuf> {
+    Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+/// Writes the placement and `images` (key and value) to the schema in `dir`, then reads the
+/// images back.
+fn write_images(dir: &str, images: &[(&str, String)], placement: &Placement) -> Result<()> {
+    let mut values = vec![
+        ("picture-options", Value::from(picture_options(placement))),
+        ("primary-color", Value::from(to_hex(placement.background))),
+        ("color-shading-type", Value::from("solid")),
+    ];
+    values.extend(
+        images
+            .iter()
+            .map(|(key, image)| (*key, Value::from(image.as_str()))),
+    );
+    dconf::write(&session_bus()?, dir, &values)?;
+    check_written(images, |key| dconf::read_string(dir, key))
+}
+
+/// Compares the images read back with `read` to what was written, as a session without dconf
+/// (or with a read-only profile) accepts the change and ignores it. Only a different value
+/// is an error: the database may be somewhere else or in a form the reader does not know,
+/// and then the change is left unverified.
+fn check_written(images: &[(&str, String)], read: impl Fn(&str) -> Option<String>) -> Result<()> {
+    for (key, expected) in images {
+        match read(key) {
+            Some(value) if value == *expected => {}
+            Some(value) => {
+                return Err(WallrusError::Backend {
+                    command: "dconf".into(),
+                    message: format!("{} is still {} after setting it", key, value),
+                })
+            }
+            None => eprintln!(
+                "Warning: could not read {} back from the dconf database, not verifying it",
+                key
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Sets the image for the light style (`picture-uri`) and the dark style
+/// (`picture-uri-dark`, GNOME 42 and later).
+fn set_gnome_images(light: &Path, dark: &Path, placement: &Placement) -> Result<()> {
+    write_images(
+        SETTINGS,
+        &[
+            ("picture-uri", uri(light)?),
+            ("picture-uri-dark", uri(dark)?),
+        ],
+        placement,
+    )
+}
+
+fn gnome_current() -> Option<PathBuf> {
+    path(&dconf::read_string(SETTINGS, "picture-uri")?)
+}
+
+impl WallpaperBackend for Gnome {
+    fn name(&self) -> &'static str {
+        "gnome"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Gnome)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        all_outputs_only(self.name(), output)?;
+        set_gnome_images(image, image, placement)
+    }
+
+    fn set_light_dark(
+        &self,
+        light: &Path,
+        dark: &Path,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        set_gnome_images(light, dark, placement)
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        gnome_current()
+    }
+}
+
+/// Budgie 10 draws the background from GNOME's settings, but its session is not GNOME.
+impl WallpaperBackend for Budgie {
+    fn name(&self) -> &'static str {
+        "budgie"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Budgie)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        all_outputs_only(self.name(), output)?;
+        set_gnome_images(image, image, placement)
+    }
+
+    fn set_light_dark(
+        &self,
+        light: &Path,
+        dark: &Path,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        set_gnome_images(light, dark, placement)
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        gnome_current()
+    }
+}
+
+impl WallpaperBackend for Cinnamon {
+    fn name(&self) -> &'static str {
+        "cinnamon"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Cinnamon)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        all_outputs_only(self.name(), output)?;
+        write_images(
+            CINNAMON_SETTINGS,
+            &[("picture-uri", uri(image)?)],
+            placement,
+        )
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        path(&dconf::read_string(CINNAMON_SETTINGS, "picture-uri")?)
+    }
+}
+
+/// MATE stores a plain path in `picture-filename` rather than a URI.
+impl WallpaperBackend for Mate {
+    fn name(&self) -> &'static str {
+        "mate"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Mate)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        all_outputs_only(self.name(), output)?;
+        let path = image.canonicalize()?.display().to_string();
+        write_images(MATE_SETTINGS, &[("picture-filename", path)], placement)
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        dconf::read_string(MATE_SETTINGS, "picture-filename").map(PathBuf::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uri_is_absolute_and_encoded() {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let expected = Url::from_file_path(manifest.canonicalize().unwrap()).unwrap();
+        assert_eq!(uri(Path::new("Cargo.toml")).unwrap(), expected.as_str());
+
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("my photo #1 100%.png");
+        std::fs::write(&image, b"").unwrap();
+        let encoded = uri(&image).unwrap();
+        assert!(encoded.starts_with("file:///"));
+        assert!(encoded.ends_with("/my%20photo%20%231%20100%25.png"));
+        assert_eq!(path(&encoded).unwrap(), image.canonicalize().unwrap());
+
+        assert!(uri(&dir.path().join("missing.png")).is_err());
+    }
+
+    #[test]
+    fn test_only_a_different_value_fails_the_check() {
+        let images = [("picture-uri", "file:///a.png".to_string())];
+        assert!(check_written(&images, |_| Some("file:///a.png".into())).is_ok());
+        assert!(check_written(&images, |_| None).is_ok());
+        assert!(check_written(&images, |_| Some("file:///b.png".into())).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! KDE Plasma 5 and 6, through scripts evaluated by plasmashell over D-Bus.
+
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+use zbus::blocking::{Connection, Proxy};
+
+use super::{bus_error, desktop_is, session_bus};
+use crate::engine::backend::WallpaperBackend;
+use crate::engine::color::to_hex;
+use crate::engine::desktop::{detected, Desktop};
+use crate::engine::{Placement, ScaleMode, Transition};
+use crate::errors::{Result, WallrusError};
+
+const SERVICE: &str = "org.kde.plasmashell";
+const PATH: &str = "/PlasmaShell";
+const INTERFACE: &str = "org.kde.PlasmaShell";
+
+pub struct Kde;
+
+/// Runs a Plasma script and returns what it printed.
+fn evaluate_script(bus: &Connection, script: &str) -> Result<String> {
+    let shell = Proxy::new(bus, SERVICE, PATH, INTERFACE).map_err(bus_error)?;
+    shell
+        .call("evaluateScript", &(script,))
+        .map_err(|e| WallrusError::Backend {
+            command: "plasmashell".into(),
+            message: e.to_string(),
+        })
+}
+
+/// The image plugin's `FillMode`, a Qt `Image.FillMode`.
+fn fill_mode(mode: ScaleMode) -> u8 {
+    match mode {
+        ScaleMode::Stretch => 0,
+        ScaleMode::Fit => 1,
+        ScaleMode::Fill => 2,
+        ScaleMode::Tile => 3,
+        ScaleMode::Center => 6,
+    }
+}
+
+/// A JavaScript string literal for `text`; JSON strings are valid JavaScript.
+fn js_string(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+/// Plasma numbers its screens from 0; `--output` takes such a number.
+fn screen(output: Option<&str>) -> Result<i64> {
+    match output {
+        None => Ok(-1),
+        Some(output) => output.parse::<u32>().map(i64::from).map_err(|_| {
+            WallrusError::Config(format!(
+                "The kde backend takes a screen number for --output, such as 0, not {:?}",
+                output
+            ))
+        }),
+    }
+}
+
+/// Loops over the desktops on `screen`, or on every screen if it is negative.
+fn for_each_desktop(screen: i64, body: &str) -> String {
+    format!(
+        "var screen = {};
+var allDesktops = desktops();
+for (var i = 0; i < allDesktops.length; i++) {{
+    var d = allDesktops[i];
+    if (screen >= 0 && d.screen != screen) continue;
+{}
+}}",
+        screen, body
+    )
+}
+
+fn set_script(uri: &str, screen: i64, placement: &Placement) -> String {
+    let body = format!(
+        "    d.wallpaperPlugin = 'org.kde.image';
+    d.currentConfigGroup = ['Wallpaper', 'org.kde.image', 'General'];
+    d.writeConfig('Image', {});
+    d.writeConfig('FillMode', {});
+    d.writeConfig('Color', {});",
+        js_string(uri),
+        fill_mode(placement.mode),
+        js_string(&to_hex(placement.background))
+    );
+    for_each_desktop(screen, &body)
+}
+
+/// Prints the image of each desktop on `screen`, one per line.
+fn images_script(screen: i64) -> String {
+    for_each_desktop(
+        screen,
+        "    d.currentConfigGroup = ['Wallpaper', 'org.kde.image', 'General'];
+    print(d.readConfig('Image'));",
+    )
+}
+
+/// The major Plasma version, from `KDE_SESSION_VERSION` or else the shell itself.
+fn plasma_version(bus: &Connection) -> Result<u32> {
+    if let Some((version, _)) = detected().plasma_version {
+        return Ok(version);
+    }
+    let version = evaluate_script(bus, "print(applicationVersion)")?;
+    version
+        .trim()
+        .split('.')
+        .next()
+        .and_then(|major| major.parse().ok())
+        .ok_or_else(|| WallrusError::Backend {
+            command: "plasmashell".into(),
+            message: format!("unexpected version {:?}", version.trim()),
+        })
+}
+
+/// Sets `image` on the desktops of `screen` (or all of them), then reads them back.
+fn set_image(bus: &Connection, image: &Path, screen: i64, placement: &Placement) -> Result<()> {
+    let version = plasma_version(bus)?;
+    if version < 5 {
+        return Err(WallrusError::Backend {
+            command: "plasmashell".into(),
+            message: format!("Plasma {} is not supported, only Plasma 5 and 6", version),
+        });
+    }
+
+    let uri = Url::from_file_path(image.canonicalize()?)
+        .map_err(|_| WallrusError::Config(format!("Invalid image path: {:?}", image)))?;
+    evaluate_script(bus, &set_script(uri.as_str(), screen, placement))?;
+
+    let images = evaluate_script(bus, &images_script(screen))?;
+    let images: Vec<_> = images.lines().filter(|line| !line.is_empty()).collect();
+    if images.is_empty() {
+        return Err(WallrusError::Backend {
+            command: "plasmashell".into(),
+            message: format!("Plasma {} has no desktop on screen {}", version, screen),
+        });
+    }
+    match images.iter().find(|current| **current != uri.as_str()) {
+        Some(current) => Err(WallrusError::Backend {
+            command: "plasmashell".into(),
+            message: format!("the wallpaper is still {} after setting it", current),
+        }),
+        None => Ok(()),
+    }
+}
+
+impl WallpaperBackend for Kde {
+    fn name(&self) -> &'static str {
+        "kde"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Kde)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        set_image(&session_bus()?, image, screen(output)?, placement)
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        let images = evaluate_script(&session_bus().ok()?, &images_script(-1)).ok()?;
+        Url::parse(images.lines().next()?).ok()?.to_file_path().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::linux::test_bus::TestBus;
+    use std::sync::{Arc, Mutex};
+
+    /// Stands in for plasmashell: records scripts and keeps the image that was written.
+    #[derive(Default)]
+    struct Shell {
+        scripts: Arc<Mutex<Vec<String>>>,
+        image: Arc<Mutex<String>>,
+    }
+
+    #[zbus::interface(name = "org.kde.PlasmaShell")]
+    impl Shell {
+        #[zbus(name = "evaluateScript")]
+        fn evaluate_script(&self, script: String) -> zbus::fdo::Result<String> {
+            self.scripts.lock().unwrap().push(script.clone());
+            if script.contains("applicationVersion") {
+                return Ok("6.1.4\n".into());
+            }
+            if script.contains("print(d.readConfig('Image'))") {
+                return Ok(format!("{}\n", self.image.lock().unwrap()));
+            }
+            let image = script
+                .split("d.writeConfig('Image', ")
+                .nth(1)
+                .and_then(|rest| rest.split(");").next())
+                .and_then(|literal| serde_json::from_str::<String>(literal).ok())
+                .ok_or_else(|| zbus::fdo::Error::Failed("ReferenceError".into()))?;
+            *self.image.lock().unwrap() = image;
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn test_set_escapes_path_and_reads_back() {
+        let bus = TestBus::start();
+        let shell = Shell::default();
+        let (scripts, image) = (shell.scripts.clone(), shell.image.clone());
+        let _service = bus.serve(SERVICE, PATH, shell);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("it's \"here\".png");
+        std::fs::write(&path, b"").unwrap();
+        let placement = Placement {
+            mode: ScaleMode::Center,
+            ..Default::default()
+        };
+        set_image(&bus.connect(), &path, 1, &placement).unwrap();
+
+        let expected = Url::from_file_path(path.canonicalize().unwrap()).unwrap();
+        assert_eq!(*image.lock().unwrap(), expected.as_str());
+        assert!(expected.as_str().ends_with("/it's%20%22here%22.png"));
+        let scripts = scripts.lock().unwrap();
+        let set = scripts
+            .iter()
+            .find(|script| script.contains("writeConfig"))
+            .unwrap();
+        assert!(set.contains("var screen = 1;"));
+        assert!(set.contains("d.writeConfig('FillMode', 6);"));
+    }
+
+    #[test]
+    fn test_screen_must_be_a_number() {
+        assert_eq!(screen(None).unwrap(), -1);
+        assert_eq!(screen(Some("1")).unwrap(), 1);
+        assert!(screen(Some("DP-1")).is_err());
+    }
+}
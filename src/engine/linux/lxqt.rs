@@ -0,0 +1,106 @@
+//! LXQt, whose desktop is drawn by pcmanfm-qt.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::desktop_is;
+use crate::engine::backend::{all_outputs_only, WallpaperBackend};
+use crate::engine::desktop::Desktop;
+use crate::engine::{Placement, ScaleMode, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::run;
+
+/// The pcmanfm-qt profile an LXQt session runs its desktop with.
+const PROFILE: &str = "lxqt";
+
+pub struct Lxqt;
+
+/// The `--wallpaper-mode` value for a scaling mode.
+fn wallpaper_mode(mode: ScaleMode) -> &'static str {
+    match mode {
+        ScaleMode::Fill => "zoom",
+        ScaleMode::Fit => "fit",
+        ScaleMode::Center => "center",
+        ScaleMode::Tile => "tile",
+        ScaleMode::Stretch => "stretch",
+    }
+}
+
+/// The `Wallpaper` entry of the `[Desktop]` group in pcmanfm-qt's `settings.conf`.
+fn wallpaper_setting(settings: &str) -> Option<&str> {
+    let mut in_desktop = false;
+    for line in settings.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_desktop = line == "[Desktop]";
+        } else if let Some(value) = line.strip_prefix("Wallpaper=").filter(|_| in_desktop) {
+            return Some(value.trim_matches('"'));
+        }
+    }
+    None
+}
+
+impl WallpaperBackend for Lxqt {
+    fn name(&self) -> &'static str {
+        "lxqt"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Lxqt)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    /// Hands the image to the running pcmanfm-qt, which saves it in its settings itself. The
+    /// path is made absolute, as pcmanfm-qt resolves it in its own working directory.
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        all_outputs_only(self.name(), output)?;
+        let image = image.canonicalize()?;
+        let image = image.to_str().ok_or_else(|| {
+            WallrusError::Config(format!(
+                "pcmanfm-qt cannot take a wallpaper path that is not UTF-8: {:?}",
+                image
+            ))
+        })?;
+        run(Command::new("pcmanfm-qt").args([
+            &format!("--profile={}", PROFILE),
+            &format!("--set-wallpaper={}", image),
+            &format!("--wallpaper-mode={}", wallpaper_mode(placement.mode)),
+        ]))?;
+        Ok(())
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        let path = dirs::config_dir()?
+            .join("pcmanfm-qt")
+            .join(PROFILE)
+            .join("settings.conf");
+        let settings = fs::read_to_string(path).ok()?;
+        wallpaper_setting(&settings)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallpaper_setting_is_in_the_desktop_group() {
+        let settings = "[System]\nWallpaper=/not/this.png\n\n[Desktop]\nBgColor=#000000\nWallpaper=/usr/share/lxqt/wallpapers/waves.png\nWallpaperMode=zoom\n";
+        assert_eq!(
+            wallpaper_setting(settings),
+            Some("/usr/share/lxqt/wallpapers/waves.png")
+        );
+        assert_eq!(wallpaper_setting("[Desktop]\nWallpaperMode=zoom\n"), None);
+    }
+}
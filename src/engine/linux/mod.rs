@@ -0,0 +1,121 @@
+//! Wallpaper backends for Linux desktops and Wayland compositors.
+
+mod dconf;
+mod deepin;
+mod enlightenment;
+mod gnome;
+mod kde;
+mod lxqt;
+#[cfg(feature = "wayland")]
+mod native;
+#[cfg(test)]
+mod test_bus;
+mod wlroots;
+#[cfg(feature = "x11")]
+mod x11;
+mod xfce;
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::engine::backend::{set_with_backends, WallpaperBackend};
+use crate::engine::desktop::{detected, Desktop};
+use crate::engine::{Placement, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::state_dir;
+
+/// Every backend this build knows, in no particular order.
+pub fn backends() -> Vec<Box<dyn WallpaperBackend>> {
+    vec![
+        #[cfg(feature = "wayland")]
+        Box::new(native::Native),
+        Box::new(gnome::Gnome),
+        Box::new(kde::Kde),
+        Box::new(xfce::Xfce),
+        Box::new(gnome::Cinnamon),
+        Box::new(gnome::Mate),
+        Box::new(gnome::Budgie),
+        Box::new(lxqt::Lxqt),
+        Box::new(deepin::Deepin),
+        Box::new(enlightenment::Enlightenment),
+        Box::new(wlroots::Hyprpaper),
+        Box::new(wlroots::Swww),
+        Box::new(wlroots::Swaybg),
+        #[cfg(feature = "x11")]
+        Box::new(x11::X11),
+    ]
+}
+
+/// Sets the wallpaper with the first backend that works: the desktop's own settings, the
+/// native layer-shell daemon or one of the wlroots tools, unless the user pinned others.
+/// Transitions are only drawn by the native Wayland backend.
+pub fn set_wallpaper(
+    image_path: &Path,
+    placement: &Placement,
+    transition: &Transition,
+) -> Result<()> {
+    set_with_backends(image_path, None, placement, transition)
+}
+
+/// Sets the wallpaper of a single output, matched by name (`DP-1`), with the first backend
+/// that can address outputs.
+pub fn set_output_wallpaper(
+    image_path: &Path,
+    output: &str,
+    placement: &Placement,
+    transition: &Transition,
+) -> Result<()> {
+    set_with_backends(image_path, Some(output), placement, transition)
+}
+
+/// Whether the detected desktop is `desktop`.
+fn desktop_is(desktop: Desktop) -> bool {
+    detected().desktop() == Some(desktop)
+}
+
+/// Whether the detected desktop draws its own background.
+#[cfg(any(feature = "wayland", feature = "x11"))]
+fn is_managed_desktop() -> bool {
+    detected()
+        .desktop()
+        .is_some_and(|desktop| desktop.draws_own_background())
+}
+
+/// Whether this is a Wayland session. Hyprland and Sway are recognized by their sockets even
+/// without `WAYLAND_DISPLAY`.
+fn is_wayland() -> bool {
+    detected().is_wayland()
+}
+
+/// Whether the next wallpaper goes through the native Wayland backend, so colors and
+/// gradients can be drawn without an image file.
+#[cfg(feature = "wayland")]
+pub fn uses_native_wayland() -> bool {
+    crate::engine::backend::first_backend().is_some_and(|backend| backend.name() == "native")
+}
+
+/// A path under `dir` in the state directory for a file generated from `key`, such as a
+/// rendered image. Each key gets its own name, as desktops keep showing a file they have
+/// already loaded.
+fn generated_path(dir: &str, name: &str, key: impl Hash, extension: &str) -> Result<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let dir = state_dir()?.join(dir);
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{}-{:016x}.{}", name, hasher.finish(), extension)))
+}
+
+/// Turns a D-Bus failure into a backend error.
+fn bus_error(e: zbus::Error) -> WallrusError {
+    WallrusError::Backend {
+        command: "D-Bus".into(),
+        message: e.to_string(),
+    }
+}
+
+/// Connects to the session bus.
+fn session_bus() -> Result<zbus::blocking::Connection> {
+    zbus::blocking::Connection::session().map_err(bus_error)
+}
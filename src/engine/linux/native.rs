@@ -0,0 +1,43 @@
+//! The built-in layer-shell daemon, for Wayland compositors that advertise wlr-layer-shell.
+
+use std::path::{Path, PathBuf};
+
+use super::is_managed_desktop;
+use crate::engine::backend::WallpaperBackend;
+use crate::engine::wayland;
+use crate::engine::{Placement, Transition};
+use crate::errors::Result;
+
+pub struct Native;
+
+impl WallpaperBackend for Native {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    /// Any compositor with wlr-layer-shell (Hyprland, Sway, river, niri, Wayfire, labwc, ...),
+    /// unless the desktop draws its own background.
+    fn is_available(&self) -> bool {
+        std::env::var("WAYLAND_DISPLAY").is_ok()
+            && !is_managed_desktop()
+            && wayland::has_layer_shell()
+    }
+
+    fn priority(&self) -> u32 {
+        100
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        transition: &Transition,
+    ) -> Result<()> {
+        wayland::set_wayland_wallpaper(image, output, placement, transition)
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        wayland::current_wallpaper()
+    }
+}
@@ -0,0 +1,69 @@
+//! A private D-Bus session bus for tests, so backends can talk to stand-in services.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+use zbus::blocking::{connection, Connection};
+use zbus::object_server::Interface;
+
+/// A bus run by `dbus-daemon`, shut down when dropped.
+pub struct TestBus {
+    daemon: Child,
+    address: String,
+}
+
+impl TestBus {
+    /// Starts a bus. Tests that use one are ignored by default, and CI runs them with
+    /// `dbus-daemon` installed.
+    pub fn start() -> Self {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("dbus-daemon is not installed");
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Self {
+            daemon,
+            address: address.trim().to_string(),
+        }
+    }
+
+    pub fn connect(&self) -> Connection {
+        connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    /// A connection to build with several names or objects.
+    pub fn builder(&self) -> connection::Builder<'_> {
+        connection::Builder::address(self.address.as_str()).unwrap()
+    }
+
+    /// Owns `name` on the bus and serves `service` at `path` until the connection is dropped.
+    pub fn serve(
+        &self,
+        name: &'static str,
+        path: &'static str,
+        service: impl Interface,
+    ) -> Connection {
+        self.builder()
+            .name(name)
+            .unwrap()
+            .serve_at(path, service)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
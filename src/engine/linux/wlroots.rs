@@ -0,0 +1,271 @@
+//! Wallpaper tools for wlroots-style compositors: hyprpaper, swww and swaybg.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use super::is_wayland;
+use crate::engine::backend::WallpaperBackend;
+use crate::engine::color::to_hex;
+use crate::engine::{Placement, ScaleMode, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::{run, runtime_dir, state_dir, wait};
+
+/// Whether `program` can be started at all; its exit status does not matter.
+fn installed(program: &str, arg: &str) -> bool {
+    !matches!(
+        run(Command::new(program).arg(arg)),
+        Err(WallrusError::Io(_))
+    )
+}
+
+fn path_str(image: &Path) -> Result<&str> {
+    image
+        .to_str()
+        .ok_or_else(|| WallrusError::Config("Invalid image path".into()))
+}
+
+/// How long swaybg has to fail before it counts as started.
+const SWAYBG_STARTUP: Duration = Duration::from_millis(200);
+
+/// swaybg's output goes here, under the state directory.
+const SWAYBG_LOG: &str = "swaybg.log";
+
+/// The swaybg started for one output records its PID in the runtime directory, in a file
+/// named with this prefix and the output.
+const SWAYBG_PID_PREFIX: &str = "wallrus-swaybg-";
+
+fn swaybg_pid_file(output: &str) -> Result<PathBuf> {
+    Ok(runtime_dir()?.join(format!(
+        "{}{}.pid",
+        SWAYBG_PID_PREFIX,
+        output.replace('/', "_")
+    )))
+}
+
+/// Stops the swaybg showing `output`, or every swaybg when setting all outputs. A recorded
+/// PID is only killed while it still belongs to a swaybg.
+fn stop_swaybg(output: Option<&str>) -> Result<()> {
+    let Some(output) = output else {
+        // pkill fails when there are none
+        let _ = run(Command::new("pkill").args(["-x", "swaybg"]));
+        if let Ok(entries) = fs::read_dir(runtime_dir()?) {
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if name.starts_with(SWAYBG_PID_PREFIX) && name.ends_with(".pid") {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+        return Ok(());
+    };
+
+    let pid_file = swaybg_pid_file(output)?;
+    let Ok(pid) = fs::read_to_string(&pid_file) else {
+        return Ok(());
+    };
+    let pid = pid.trim();
+    let comm = fs::read_to_string(Path::new("/proc").join(pid).join("comm")).unwrap_or_default();
+    if pid.parse::<u32>().is_ok() && comm.trim_end() == "swaybg" {
+        let _ = run(Command::new("kill").arg(pid));
+    }
+    let _ = fs::remove_file(pid_file);
+    Ok(())
+}
+
+pub struct Hyprpaper;
+
+impl WallpaperBackend for Hyprpaper {
+    fn name(&self) -> &'static str {
+        "hyprpaper"
+    }
+
+    fn is_available(&self) -> bool {
+        is_wayland() && installed("hyprctl", "--help")
+    }
+
+    fn priority(&self) -> u32 {
+        50
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        let image = path_str(image)?;
+        // First preload the image (required by hyprpaper before 0.7, which rejects a second
+        // preload of the same image)
+        let _ = hyprctl(&["hyprpaper", "preload", image]);
+
+        // Then set as wallpaper; hyprpaper covers the screen unless told to contain or tile
+        let mode = match placement.mode {
+            ScaleMode::Fit => "contain:",
+            ScaleMode::Tile => "tile:",
+            _ => "",
+        };
+        hyprctl(&[
+            "hyprpaper",
+            "wallpaper",
+            &format!("{},{}{}", output.unwrap_or_default(), mode, image),
+        ])
+    }
+
+    /// The first line of `hyprctl hyprpaper listactive`, `DP-1 = /path/to/image`.
+    fn current(&self) -> Option<PathBuf> {
+        let listing = run(Command::new("hyprctl").args(["hyprpaper", "listactive"])).ok()?;
+        let (_, path) = listing.lines().next()?.split_once(" = ")?;
+        Some(PathBuf::from(path.trim()))
+    }
+}
+
+/// Runs a hyprctl command. hyprctl exits successfully even when hyprpaper rejects the
+/// request, so anything but `ok` is an error.
+fn hyprctl(args: &[&str]) -> Result<()> {
+    let reply = run(Command::new("hyprctl").args(args))?;
+    match reply.trim() {
+        "ok" => Ok(()),
+        reply => Err(WallrusError::Backend {
+            command: "hyprctl".into(),
+            message: reply.to_string(),
+        }),
+    }
+}
+
+pub struct Swww;
+
+impl WallpaperBackend for Swww {
+    fn name(&self) -> &'static str {
+        "swww"
+    }
+
+    fn is_available(&self) -> bool {
+        is_wayland() && installed("swww", "--version")
+    }
+
+    fn priority(&self) -> u32 {
+        40
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        let resize = match placement.mode {
+            ScaleMode::Fit => "fit",
+            ScaleMode::Center => "no",
+            _ => "crop",
+        };
+        let fill_color = to_hex(placement.background);
+        let mut command = Command::new("swww");
+        command.args([
+            "img",
+            "--resize",
+            resize,
+            "--fill-color",
+            fill_color.trim_start_matches('#'),
+        ]);
+        if let Some(output) = output {
+            command.args(["--outputs", output]);
+        }
+        run(command.arg(path_str(image)?))?;
+        Ok(())
+    }
+
+    /// The first output in `swww query`, which ends in `currently displaying: image: /path`.
+    fn current(&self) -> Option<PathBuf> {
+        let listing = run(Command::new("swww").arg("query")).ok()?;
+        let (_, path) = listing.lines().next()?.split_once("image: ")?;
+        Some(PathBuf::from(path.trim()))
+    }
+}
+
+pub struct Swaybg;
+
+impl WallpaperBackend for Swaybg {
+    fn name(&self) -> &'static str {
+        "swaybg"
+    }
+
+    fn is_available(&self) -> bool {
+        is_wayland() && installed("swaybg", "--version")
+    }
+
+    fn priority(&self) -> u32 {
+        30
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        stop_swaybg(output)?;
+
+        // Start new swaybg instance, logging to a file as it outlives wallrus
+        let log_path = state_dir()?.join(SWAYBG_LOG);
+        if let Some(dir) = log_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut command = Command::new("swaybg");
+        if let Some(output) = output {
+            command.args(["-o", output]);
+        }
+        let mut child = command
+            .args([
+                "-i",
+                path_str(image)?,
+                "-m",
+                swaybg_mode(placement.mode),
+                "-c",
+                &to_hex(placement.background),
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(File::create(&log_path)?)
+            .spawn()?;
+
+        // swaybg keeps running; if it exits within a moment, it failed
+        match wait(&mut child, SWAYBG_STARTUP)? {
+            Some(status) => {
+                let stderr = fs::read_to_string(&log_path).unwrap_or_default();
+                Err(WallrusError::Backend {
+                    command: "swaybg".into(),
+                    message: match stderr.trim() {
+                        "" => status.to_string(),
+                        stderr => stderr.to_string(),
+                    },
+                })
+            }
+            None => {
+                if let Some(output) = output {
+                    fs::write(swaybg_pid_file(output)?, child.id().to_string())?;
+                }
+                // A slideshow or the daemon outlives many swaybg instances; reap each one
+                // when the next wallpaper stops it, so they do not linger as zombies.
+                thread::spawn(move || child.wait());
+                Ok(())
+            }
+        }
+    }
+}
+
+fn swaybg_mode(mode: ScaleMode) -> &'static str {
+    match mode {
+        ScaleMode::Fill => "fill",
+        ScaleMode::Fit => "fit",
+        ScaleMode::Center => "center",
+        ScaleMode::Tile => "tile",
+        ScaleMode::Stretch => "stretch",
+    }
+}
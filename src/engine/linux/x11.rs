@@ -0,0 +1,509 @@
+//! Plain X11 window managers (i3, bspwm, openbox, awesome, ...): the wallpaper is drawn into
+//! a pixmap that becomes the root window's background, one region per RandR monitor.
+
+use std::path::Path;
+
+use image::{Rgba, RgbaImage};
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::randr::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, CloseDown, ConnectionExt as _, CreateGCAux,
+    ImageFormat, ImageOrder, Pixmap, PropMode, Screen, Setup, Window,
+};
+use x11rb::wrapper::ConnectionExt as _;
+
+use super::{is_managed_desktop, is_wayland};
+use crate::engine::backend::WallpaperBackend;
+use crate::engine::{scaling, Placement, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::open_image;
+
+/// Root window properties that name the background pixmap, read by compositors and
+/// pseudo-transparent terminals. `ESETROOT_PMAP_ID` also marks a pixmap that the next
+/// setter may free.
+const ROOT_PIXMAP: &str = "_XROOTPMAP_ID";
+const ESETROOT_PIXMAP: &str = "ESETROOT_PMAP_ID";
+
+pub struct X11;
+
+/// A monitor's region of the root window.
+#[derive(Debug, Clone, PartialEq)]
+struct Monitor {
+    name: String,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// How the root window's visual packs a pixel into 32 bits.
+#[derive(Debug, Clone, Copy)]
+struct PixelFormat {
+    depth: u8,
+    red_shift: u32,
+    green_shift: u32,
+    blue_shift: u32,
+    big_endian: bool,
+}
+
+impl PixelFormat {
+    /// The format of `screen`'s root visual. Only TrueColor visuals with 8 bits per
+    /// channel in 32-bit pixels are handled, which covers depth 24 and 32 servers.
+    fn of(setup: &Setup, screen: &Screen) -> Result<Self> {
+        let unsupported = |what: String| WallrusError::Backend {
+            command: "X11".into(),
+            message: format!("unsupported root window format: {}", what),
+        };
+        let bits = setup
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == screen.root_depth)
+            .map(|format| format.bits_per_pixel);
+        if bits != Some(32) {
+            return Err(unsupported(format!(
+                "depth {} with {:?} bits per pixel",
+                screen.root_depth, bits
+            )));
+        }
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .flat_map(|depth| &depth.visuals)
+            .find(|visual| visual.visual_id == screen.root_visual)
+            .ok_or_else(|| unsupported("no root visual".into()))?;
+        let shift = |mask: u32| match mask.trailing_zeros() {
+            shift if shift < 32 && mask >> shift == 0xff => Ok(shift),
+            _ => Err(unsupported(format!("color mask {:#x}", mask))),
+        };
+        Ok(Self {
+            depth: screen.root_depth,
+            red_shift: shift(visual.red_mask)?,
+            green_shift: shift(visual.green_mask)?,
+            blue_shift: shift(visual.blue_mask)?,
+            big_endian: setup.image_byte_order == ImageOrder::MSB_FIRST,
+        })
+    }
+
+    fn encode(&self, pixel: &Rgba<u8>) -> [u8; 4] {
+        let [r, g, b, _] = pixel.0;
+        let value = (r as u32) << self.red_shift
+            | (g as u32) << self.green_shift
+            | (b as u32) << self.blue_shift;
+        if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+}
+
+/// Draws `image` onto a canvas the size of the root window: into every monitor, only the
+/// one named `output`, or across the box around all monitors when spanning. Also returns
+/// the regions of the canvas that changed.
+fn compose(
+    image: &RgbaImage,
+    (width, height): (u32, u32),
+    monitors: &[Monitor],
+    output: Option<&str>,
+    placement: &Placement,
+) -> Result<(RgbaImage, Vec<Monitor>)> {
+    let (r, g, b) = placement.background;
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+    let screen = Monitor {
+        name: String::new(),
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+
+    let targets: Vec<Monitor> = match output {
+        Some(_) if placement.span => {
+            return Err(WallrusError::Config(
+                "--span covers every monitor and cannot be combined with --output".into(),
+            ))
+        }
+        Some(output) => match monitors.iter().find(|monitor| monitor.name == output) {
+            Some(monitor) => vec![monitor.clone()],
+            None => {
+                let names: Vec<_> = monitors
+                    .iter()
+                    .map(|monitor| monitor.name.as_str())
+                    .collect();
+                return Err(WallrusError::Config(format!(
+                    "No X11 monitor named {}, only: {}",
+                    output,
+                    names.join(", ")
+                )));
+            }
+        },
+        None if placement.span && !monitors.is_empty() => {
+            let left = monitors.iter().map(|m| m.x).min().unwrap_or(0);
+            let top = monitors.iter().map(|m| m.y).min().unwrap_or(0);
+            let right = monitors
+                .iter()
+                .map(|m| m.x + m.width as i32)
+                .max()
+                .unwrap_or(0);
+            let bottom = monitors
+                .iter()
+                .map(|m| m.y + m.height as i32)
+                .max()
+                .unwrap_or(0);
+            vec![Monitor {
+                name: String::new(),
+                x: left,
+                y: top,
+                width: (right - left) as u32,
+                height: (bottom - top) as u32,
+            }]
+        }
+        None if monitors.is_empty() => vec![screen.clone()],
+        None => monitors.to_vec(),
+    };
+
+    for target in &targets {
+        let rendered = scaling::render(image, target.width, target.height, placement);
+        image::imageops::replace(&mut canvas, &rendered, target.x as u32, target.y as u32);
+    }
+    // Without an output the whole root window is new, including the gaps between monitors.
+    let changed = if output.is_some() {
+        targets
+    } else {
+        vec![screen]
+    };
+    Ok((canvas, changed))
+}
+
+/// The RandR 1.5 monitors of `root`, empty where the server does not have them.
+fn monitors(
+    conn: &impl Connection,
+    root: Window,
+) -> std::result::Result<Vec<Monitor>, ReplyOrIdError> {
+    if conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        return Ok(Vec::new());
+    }
+    let version = conn.randr_query_version(1, 5)?.reply()?;
+    if (version.major_version, version.minor_version) < (1, 5) {
+        return Ok(Vec::new());
+    }
+    let mut monitors = Vec::new();
+    for info in conn.randr_get_monitors(root, true)?.reply()?.monitors {
+        let name = conn.get_atom_name(info.name)?.reply()?.name;
+        monitors.push(Monitor {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            x: info.x.into(),
+            y: info.y.into(),
+            width: info.width.into(),
+            height: info.height.into(),
+        });
+    }
+    Ok(monitors)
+}
+
+fn atom(conn: &impl Connection, name: &str) -> std::result::Result<Atom, ReplyOrIdError> {
+    Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+}
+
+/// The pixmap a root window property names, if it still exists.
+fn root_pixmap(
+    conn: &impl Connection,
+    root: Window,
+    property: Atom,
+) -> std::result::Result<Option<Pixmap>, ReplyOrIdError> {
+    let reply = conn
+        .get_property(false, root, property, AtomEnum::PIXMAP, 0, 1)?
+        .reply()?;
+    let Some(pixmap) = reply.value32().and_then(|mut values| values.next()) else {
+        return Ok(None);
+    };
+    Ok(conn.get_geometry(pixmap)?.reply().ok().map(|_| pixmap))
+}
+
+/// Makes a new root pixmap from `canvas`, uploading only the `changed` regions on top of
+/// the current one, and hands it to the root window.
+fn set_root_pixmap(
+    conn: &impl Connection,
+    screen: &Screen,
+    format: PixelFormat,
+    canvas: &RgbaImage,
+    changed: &[Monitor],
+) -> std::result::Result<(), ReplyOrIdError> {
+    let root = screen.root;
+    let (width, height) = (screen.width_in_pixels, screen.height_in_pixels);
+    let (root_atom, esetroot_atom) = (atom(conn, ROOT_PIXMAP)?, atom(conn, ESETROOT_PIXMAP)?);
+    let old = root_pixmap(conn, root, root_atom)?;
+
+    let pixmap = conn.generate_id()?;
+    conn.create_pixmap(format.depth, pixmap, root, width, height)?;
+    let gc = conn.generate_id()?;
+    conn.create_gc(gc, pixmap, &CreateGCAux::new())?;
+    let whole = Monitor {
+        name: String::new(),
+        x: 0,
+        y: 0,
+        width: width.into(),
+        height: height.into(),
+    };
+    let regions = match old {
+        Some(old) => {
+            conn.copy_area(old, pixmap, gc, 0, 0, 0, 0, width, height)?;
+            changed
+        }
+        None => std::slice::from_ref(&whole),
+    };
+
+    // Upload in bands of rows that fit in a request.
+    let header = 32;
+    for region in regions {
+        let row_bytes = region.width as usize * 4;
+        let rows = ((conn.maximum_request_bytes() - header) / row_bytes).max(1) as u32;
+        for top in (0..region.height).step_by(rows as usize) {
+            let band = rows.min(region.height - top);
+            let y = region.y as u32 + top;
+            let mut data = Vec::with_capacity(row_bytes * band as usize);
+            for row in y..y + band {
+                for column in region.x as u32..region.x as u32 + region.width {
+                    data.extend_from_slice(&format.encode(canvas.get_pixel(column, row)));
+                }
+            }
+            conn.put_image(
+                ImageFormat::Z_PIXMAP,
+                pixmap,
+                gc,
+                region.width as u16,
+                band as u16,
+                region.x as i16,
+                y as i16,
+                0,
+                format.depth,
+                &data,
+            )?;
+        }
+    }
+    conn.free_gc(gc)?;
+
+    // A previous setter that marked its pixmap as free to take is killed to release it.
+    let esetroot = root_pixmap(conn, root, esetroot_atom)?;
+    for property in [root_atom, esetroot_atom] {
+        conn.change_property32(
+            PropMode::REPLACE,
+            root,
+            property,
+            AtomEnum::PIXMAP,
+            &[pixmap],
+        )?;
+    }
+    conn.change_window_attributes(
+        root,
+        &ChangeWindowAttributesAux::new().background_pixmap(pixmap),
+    )?;
+    conn.clear_area(false, root, 0, 0, 0, 0)?;
+    if let Some(old) = old.filter(|old| esetroot == Some(*old)) {
+        conn.kill_client(old)?;
+    }
+
+    // The pixmap has to outlive this connection.
+    conn.set_close_down_mode(CloseDown::RETAIN_PERMANENT)?;
+    conn.get_input_focus()?.reply()?;
+    Ok(())
+}
+
+fn x11_error(e: impl std::fmt::Display) -> WallrusError {
+    WallrusError::Backend {
+        command: "X11".into(),
+        message: e.to_string(),
+    }
+}
+
+/// Sets `image` as the background of the root window of `screen`.
+fn set_image(
+    conn: &impl Connection,
+    screen: usize,
+    image: &Path,
+    output: Option<&str>,
+    placement: &Placement,
+) -> Result<()> {
+    let setup = conn.setup();
+    let screen = &setup.roots[screen];
+    let format = PixelFormat::of(setup, screen)?;
+    let monitors = monitors(conn, screen.root).map_err(x11_error)?;
+    let source = open_image(image)?.to_rgba8();
+    let size = (
+        screen.width_in_pixels.into(),
+        screen.height_in_pixels.into(),
+    );
+    let (canvas, changed) = compose(&source, size, &monitors, output, placement)?;
+    set_root_pixmap(conn, screen, format, &canvas, &changed).map_err(x11_error)
+}
+
+impl WallpaperBackend for X11 {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    /// An X11 session without a desktop that draws its own background.
+    fn is_available(&self) -> bool {
+        std::env::var_os("DISPLAY").is_some() && !is_wayland() && !is_managed_desktop()
+    }
+
+    fn priority(&self) -> u32 {
+        20
+    }
+
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        let (conn, screen) = x11rb::connect(None).map_err(x11_error)?;
+        set_image(&conn, screen, image, output, placement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{ScaleFilter, ScaleMode};
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    fn monitor(name: &str, x: i32, width: u32) -> Monitor {
+        Monitor {
+            name: name.into(),
+            x,
+            y: 0,
+            width,
+            height: 2,
+        }
+    }
+
+    /// A 2x1 image, red on the left and blue on the right.
+    fn halves() -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([0, 0, 255, 255]));
+        image
+    }
+
+    fn stretch(span: bool) -> Placement {
+        Placement {
+            mode: ScaleMode::Stretch,
+            filter: ScaleFilter::Nearest,
+            span,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compose_per_monitor_or_spanned() {
+        let monitors = [monitor("DP-1", 0, 4), monitor("HDMI-1", 4, 4)];
+        let red = Rgba([255, 0, 0, 255]);
+        let blue = Rgba([0, 0, 255, 255]);
+
+        let (canvas, changed) =
+            compose(&halves(), (8, 2), &monitors, None, &stretch(false)).unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(
+            [0, 3, 4, 7].map(|x| *canvas.get_pixel(x, 0)),
+            [red, blue, red, blue]
+        );
+
+        let (canvas, _) = compose(&halves(), (8, 2), &monitors, None, &stretch(true)).unwrap();
+        assert_eq!(
+            [0, 3, 4, 7].map(|x| *canvas.get_pixel(x, 0)),
+            [red, red, blue, blue]
+        );
+
+        let (_, changed) = compose(
+            &halves(),
+            (8, 2),
+            &monitors,
+            Some("HDMI-1"),
+            &stretch(false),
+        )
+        .unwrap();
+        assert_eq!(changed, [monitors[1].clone()]);
+        assert!(compose(&halves(), (8, 2), &monitors, Some("VGA-1"), &stretch(false)).is_err());
+        assert!(compose(&halves(), (8, 2), &monitors, Some("DP-1"), &stretch(true)).is_err());
+    }
+
+    /// An Xvfb server, stopped when dropped.
+    struct Xvfb(Child, String);
+
+    impl Xvfb {
+        /// Starts Xvfb with one screen.
+        fn start(width: u32, height: u32) -> Self {
+            let mut child = Command::new("Xvfb")
+                .args(["-displayfd", "1", "-nolisten", "tcp", "-screen", "0"])
+                .arg(format!("{}x{}x24", width, height))
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("Xvfb is not installed");
+            let mut display = String::new();
+            BufReader::new(child.stdout.take().unwrap())
+                .read_line(&mut display)
+                .unwrap();
+            Self(child, format!(":{}", display.trim()))
+        }
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    /// The pixel at `x`, `y` of the pixmap the root window names, as red, green and blue.
+    fn root_pixel(conn: &impl Connection, screen: &Screen, x: i16, y: i16) -> [u8; 3] {
+        let property = atom(conn, ROOT_PIXMAP).unwrap();
+        let pixmap = root_pixmap(conn, screen.root, property).unwrap().unwrap();
+        let image = conn
+            .get_image(ImageFormat::Z_PIXMAP, pixmap, x, y, 1, 1, !0)
+            .unwrap()
+            .reply()
+            .unwrap();
+        let format = PixelFormat::of(conn.setup(), screen).unwrap();
+        let bytes: [u8; 4] = image.data[..4].try_into().unwrap();
+        let value = if format.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        };
+        [format.red_shift, format.green_shift, format.blue_shift]
+            .map(|shift| (value >> shift) as u8)
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb"]
+    fn test_root_pixmap_under_xvfb() {
+        let server = Xvfb::start(64, 32);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("halves.png");
+        halves().save(&path).unwrap();
+
+        // Each setter keeps its pixmap alive after it disconnects, so check from another.
+        let (conn, screen_num) = x11rb::connect(Some(&server.1)).unwrap();
+        let (first, _) = x11rb::connect(Some(&server.1)).unwrap();
+        set_image(&first, screen_num, &path, None, &stretch(false)).unwrap();
+        drop(first);
+        let screen = &conn.setup().roots[screen_num];
+        assert_eq!(root_pixel(&conn, screen, 0, 0), [255, 0, 0]);
+        assert_eq!(root_pixel(&conn, screen, 63, 31), [0, 0, 255]);
+
+        // Both properties name the new pixmap, and setting again frees the old one.
+        let pixmap = root_pixmap(&conn, screen.root, atom(&conn, ESETROOT_PIXMAP).unwrap())
+            .unwrap()
+            .unwrap();
+        let (again, _) = x11rb::connect(Some(&server.1)).unwrap();
+        set_image(&again, screen_num, &path, None, &stretch(false)).unwrap();
+        assert!(conn.get_geometry(pixmap).unwrap().reply().is_err());
+    }
+}
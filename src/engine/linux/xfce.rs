@@ -0,0 +1,167 @@
+//! XFCE, through the `xfce4-desktop` xfconf channel.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::desktop_is;
+use crate::engine::backend::WallpaperBackend;
+use crate::engine::desktop::Desktop;
+use crate::engine::{Placement, ScaleMode, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::run;
+
+const CHANNEL: &str = "xfce4-desktop";
+
+/// Backdrops are created here when xfdesktop has not listed any yet.
+const DEFAULT_BACKDROP: &str = "/backdrop/screen0/monitor0/workspace0";
+
+pub struct Xfce;
+
+fn xfconf_query(args: &[&str]) -> Result<String> {
+    run(Command::new("xfconf-query")
+        .args(["-c", CHANNEL])
+        .args(args))
+}
+
+/// The backdrops (such as `/backdrop/screen0/monitorDP-1/workspace0`) that have a
+/// `last-image` in a property listing, only those of `output` if one is given.
+fn backdrops(listing: &str, output: Option<&str>) -> Vec<String> {
+    listing
+        .lines()
+        .filter_map(|property| property.trim().strip_suffix("/last-image"))
+        .filter(|backdrop| match output {
+            Some(output) => backdrop
+                .split('/')
+                .any(|part| part.strip_prefix("monitor") == Some(output)),
+            None => true,
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// The `image-style` value for a placement.
+fn image_style(placement: &Placement) -> &'static str {
+    if placement.span {
+        return "6";
+    }
+    match placement.mode {
+        ScaleMode::Center => "1",
+        ScaleMode::Tile => "2",
+        ScaleMode::Stretch => "3",
+        ScaleMode::Fit => "4",
+        ScaleMode::Fill => "5",
+    }
+}
+
+/// Sets a property, creating it if needed, and reads it back.
+fn set_property(property: &str, kind: &str, value: &str) -> Result<()> {
+    xfconf_query(&["-p", property, "--create", "-t", kind, "-s", value])?;
+    let stored = xfconf_query(&["-p", property])?;
+    if stored.trim() != value {
+        return Err(WallrusError::Backend {
+            command: "xfconf-query".into(),
+            message: format!("{} is {:?} instead of {:?}", property, stored.trim(), value),
+        });
+    }
+    Ok(())
+}
+
+impl WallpaperBackend for Xfce {
+    fn name(&self) -> &'static str {
+        "xfce"
+    }
+
+    fn is_available(&self) -> bool {
+        desktop_is(Desktop::Xfce)
+    }
+
+    fn priority(&self) -> u32 {
+        90
+    }
+
+    /// Sets every backdrop xfdesktop knows (each monitor and workspace), or those of one
+    /// monitor by its connector name.
+    fn set(
+        &self,
+        image: &Path,
+        output: Option<&str>,
+        placement: &Placement,
+        _transition: &Transition,
+    ) -> Result<()> {
+        let image = image.to_str().ok_or_else(|| {
+            WallrusError::Config(format!(
+                "XFCE cannot store a wallpaper path that is not UTF-8: {:?}",
+                image
+            ))
+        })?;
+        let mut targets = backdrops(&xfconf_query(&["-l"])?, output);
+        if targets.is_empty() {
+            targets.push(match output {
+                Some(output) => format!("/backdrop/screen0/monitor{}/workspace0", output),
+                None => DEFAULT_BACKDROP.to_string(),
+            });
+        }
+
+        for backdrop in targets {
+            set_property(
+                &format!("{}/image-style", backdrop),
+                "int",
+                image_style(placement),
+            )?;
+            set_property(&format!("{}/last-image", backdrop), "string", image)?;
+        }
+        Ok(())
+    }
+
+    fn current(&self) -> Option<PathBuf> {
+        let backdrop = backdrops(&xfconf_query(&["-l"]).ok()?, None)
+            .into_iter()
+            .next()?;
+        let path = xfconf_query(&["-p", &format!("{}/last-image", backdrop)]).ok()?;
+        let path = path.trim();
+        (!path.is_empty()).then(|| PathBuf::from(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: &str = "/backdrop/screen0/monitor0/workspace0/last-image
+/backdrop/screen0/monitorDP-1/workspace0/color-style
+/backdrop/screen0/monitorDP-1/workspace0/image-style
+/backdrop/screen0/monitorDP-1/workspace0/last-image
+/backdrop/screen0/monitorDP-1/workspace1/last-image
+/backdrop/screen0/monitoreDP-1/workspace0/last-image
+/backdrop/single-workspace-mode
+";
+
+    #[test]
+    fn test_backdrops_from_listing() {
+        assert_eq!(backdrops(LISTING, None).len(), 4);
+        assert_eq!(
+            backdrops(LISTING, Some("DP-1")),
+            [
+                "/backdrop/screen0/monitorDP-1/workspace0",
+                "/backdrop/screen0/monitorDP-1/workspace1"
+            ]
+        );
+        assert_eq!(
+            backdrops(LISTING, Some("0")),
+            ["/backdrop/screen0/monitor0/workspace0"]
+        );
+        assert!(backdrops(LISTING, Some("HDMI-1")).is_empty());
+    }
+
+    #[test]
+    fn test_non_utf8_path_is_an_error() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/\xff.png"));
+        let error = Xfce
+            .set(path, None, &Placement::default(), &Transition::default())
+            .unwrap_err();
+        assert!(matches!(error, WallrusError::Config(_)));
+    }
+}
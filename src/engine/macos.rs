@@ -1,8 +1,15 @@
+use crate::engine::{Placement, Transition};
 use crate::errors::{Result, WallrusError};
+use crate::utils::run;
 use std::path::Path;
 use std::process::Command;
 
-pub fn set_wallpaper(image_path: &Path) -> Result<()> {
+/// Finder offers no control over scaling or transitions, so neither is applied on macOS.
+pub fn set_wallpaper(
+    image_path: &Path,
+    _placement: &Placement,
+    _transition: &Transition,
+) -> Result<()> {
     if !image_path.exists() {
         return Err(WallrusError::Config(format!(
             "Wallpaper file does not exist: {:?}",
@@ -16,10 +23,7 @@ pub fn set_wallpaper(image_path: &Path) -> Result<()> {
         image_path.display()
     );
 
-    Command::new("osascript")
-        .args(&["-e", &script])
-        .output()
-        .map_err(|e| WallrusError::Io(e))?;
+    run(Command::new("osascript").args(&["-e", &script]))?;
 
     Ok(())
 }
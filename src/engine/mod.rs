@@ -1,19 +1,44 @@
 // src/wallpaper/mod.rs
+mod backend;
+pub mod color;
 mod common;
+pub mod control;
+pub mod desktop;
 mod generators;
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
-#[cfg(target_os = "windows")]
-mod windows;
+mod paint;
+pub mod palette;
+mod scaling;
+mod slideshow;
+pub mod transition;
+mod watcher;
 #[cfg(all(unix, feature = "wayland"))]
 pub mod wayland;
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(not(all(unix, feature = "wayland")))]
+mod without_wayland;
 
+pub use backend::{list_backends, prefer_backends, set_light_dark_wallpaper};
 pub use common::*;
 #[cfg(target_os = "linux")]
-pub use linux::set_wallpaper;
+pub use linux::{set_output_wallpaper, set_wallpaper};
 #[cfg(target_os = "macos")]
 pub use macos::set_wallpaper;
+pub use paint::{set_paint, Paint};
+pub use scaling::{Anchor, Placement, ScaleFilter, ScaleMode};
+pub use slideshow::{run_slideshow, Order, SlideshowOptions, SortKey, WeightBy};
+pub use transition::{Easing, Point, Transition, TransitionKind};
+#[cfg(all(unix, not(target_os = "linux"), feature = "wayland"))]
+pub use wayland::set_output_wallpaper;
+#[cfg(all(unix, feature = "wayland"))]
+pub use wayland::{list_outputs, run_daemon, set_output_paint, stop_daemon};
 #[cfg(target_os = "windows")]
 pub use windows::set_wallpaper;
+#[cfg(not(any(target_os = "linux", all(unix, feature = "wayland"))))]
+pub use without_wayland::set_output_wallpaper;
+#[cfg(not(all(unix, feature = "wayland")))]
+pub use without_wayland::{list_outputs, run_daemon, set_output_paint, stop_daemon};
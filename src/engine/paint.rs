@@ -0,0 +1,190 @@
+//! Solid colors and gradients, set without an image file.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::color::{to_hex, Rgb};
+use super::{set_output_paint, set_wallpaper, Placement, ScaleMode, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::state_dir;
+
+/// Generated images for desktops that need a file go here, under the state directory.
+const PAINT_DIR: &str = "paint";
+
+/// Size of the generated gradient image. Desktops stretch it to the screen, which keeps the
+/// gradient smooth.
+const GRADIENT_SIZE: u32 = 256;
+
+/// A wallpaper drawn from colors alone.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Paint {
+    Color(Rgb),
+    /// A linear gradient. The angle is in degrees: 0 runs left to right, 90 top to bottom.
+    Gradient {
+        from: Rgb,
+        to: Rgb,
+        angle: f64,
+    },
+}
+
+impl Paint {
+    /// Draws the paint onto a `width` x `height` screen.
+    pub fn render(&self, width: u32, height: u32) -> RgbaImage {
+        let (from, to, angle) = match *self {
+            Self::Color((r, g, b)) => {
+                return RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]))
+            }
+            Self::Gradient { from, to, angle } => (from, to, angle),
+        };
+
+        // Each pixel is placed along the gradient by its projection onto the direction,
+        // which runs from the corner furthest back to the one furthest ahead.
+        let (dy, dx) = angle.to_radians().sin_cos();
+        let (w, h) = (width as f64, height as f64);
+        let corners = [0.0, w * dx, h * dy, w * dx + h * dy];
+        let start = corners.iter().copied().fold(f64::INFINITY, f64::min);
+        let end = corners.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let length = (end - start).max(f64::EPSILON);
+
+        let mix = |a: u8, b: u8, t: f64| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        let mut canvas = RgbaImage::new(width, height);
+        if width == 0 || height == 0 {
+            return canvas;
+        }
+        canvas
+            .par_chunks_mut(width as usize * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    let position = (x as f64 + 0.5) * dx + (y as f64 + 0.5) * dy;
+                    let t = ((position - start) / length).clamp(0.0, 1.0);
+                    pixel.copy_from_slice(&[
+                        mix(from.0, to.0, t),
+                        mix(from.1, to.1, t),
+                        mix(from.2, to.2, t),
+                        255,
+                    ]);
+                }
+            });
+        canvas
+    }
+
+    /// Saves a small image of the paint in `dir` for desktops that only take files, and
+    /// returns its path. The same paint always maps to the same file.
+    pub fn write_image(&self, dir: &Path) -> Result<PathBuf> {
+        let (name, image) = match *self {
+            Self::Color(color) => (
+                format!("color-{}.png", hex_digits(color)),
+                self.render(1, 1),
+            ),
+            Self::Gradient { from, to, angle } => (
+                format!(
+                    "gradient-{}-{}-{}.png",
+                    hex_digits(from),
+                    hex_digits(to),
+                    angle
+                ),
+                self.render(GRADIENT_SIZE, GRADIENT_SIZE),
+            ),
+        };
+        fs::create_dir_all(dir)?;
+        let path = dir.join(name);
+        image.save(&path).map_err(|e| {
+            WallrusError::ImageProcessing(format!("Failed to save {:?}: {}", path, e))
+        })?;
+        Ok(path)
+    }
+}
+
+fn hex_digits(color: Rgb) -> String {
+    to_hex(color).trim_start_matches('#').to_string()
+}
+
+impl fmt::Display for Paint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Color(color) => write!(f, "{}", to_hex(color)),
+            Self::Gradient { from, to, angle } => {
+                write!(
+                    f,
+                    "gradient {} to {} at {}°",
+                    to_hex(from),
+                    to_hex(to),
+                    angle
+                )
+            }
+        }
+    }
+}
+
+/// Sets a solid color or gradient as the wallpaper, on one output or on all of them. The
+/// native Wayland backend draws it directly; other desktops get a small generated image,
+/// stretched to the screen.
+pub fn set_paint(paint: &Paint, output: Option<&str>, transition: &Transition) -> Result<()> {
+    if let Some(output) = output {
+        return set_output_paint(paint, output, transition);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "wayland"))]
+    if super::linux::uses_native_wayland() {
+        match super::wayland::set_wayland_paint(paint, None, transition) {
+            Ok(()) => return Ok(()),
+            Err(e) => eprintln!("Warning: native Wayland failed, using an image: {}", e),
+        }
+    }
+
+    let image = paint.write_image(&state_dir()?.join(PAINT_DIR))?;
+    let placement = Placement {
+        mode: ScaleMode::Stretch,
+        ..Default::default()
+    };
+    set_wallpaper(&image, &placement, transition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gradient_runs_along_angle() {
+        let gradient = Paint::Gradient {
+            from: (0, 0, 0),
+            to: (200, 100, 0),
+            angle: 90.0,
+        };
+        let image = gradient.render(4, 4);
+        // Top to bottom: rows are uniform and darkest at the top.
+        assert_eq!(image.get_pixel(0, 0), image.get_pixel(3, 0));
+        assert_eq!(image.get_pixel(0, 0), &Rgba([25, 13, 0, 255]));
+        assert_eq!(image.get_pixel(0, 3), &Rgba([175, 88, 0, 255]));
+
+        let horizontal = Paint::Gradient {
+            from: (0, 0, 0),
+            to: (200, 100, 0),
+            angle: 0.0,
+        };
+        let image = horizontal.render(4, 1);
+        assert!(image
+            .pixels()
+            .zip(image.pixels().skip(1))
+            .all(|(a, b)| a[0] < b[0]));
+    }
+
+    #[test]
+    fn test_write_image_names_files_by_paint() {
+        let dir = std::env::temp_dir().join(format!("wallrus-paint-{}", std::process::id()));
+        let path = Paint::Color((32, 32, 32)).write_image(&dir).unwrap();
+        assert_eq!(path.file_name().unwrap(), "color-202020.png");
+        assert_eq!(
+            image::open(&path).unwrap().to_rgba8().get_pixel(0, 0),
+            &Rgba([32, 32, 32, 255])
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
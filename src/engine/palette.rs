@@ -0,0 +1,207 @@
+//! Dominant color extraction using k-means clustering in CIE L*a*b* space.
+
+use std::cmp::Reverse;
+use std::path::Path;
+
+use image::RgbaImage;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::color::{delta_e, lab_to_rgb, rgb_to_lab, Lab, Rgb};
+use crate::errors::Result;
+use crate::utils::open_image;
+
+/// Images are downsampled to at most this many pixels per side before clustering.
+const THUMBNAIL_SIZE: u32 = 96;
+const MAX_ITERATIONS: usize = 20;
+/// Clusters closer than this (delta E) are reported as a single color.
+const MERGE_DISTANCE: f32 = 4.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaletteColor {
+    pub rgb: Rgb,
+    /// Share of the image covered by this color, between 0 and 1.
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    /// Dominant colors, most common first.
+    pub colors: Vec<PaletteColor>,
+    /// Average perceptual lightness (L* / 100), between 0 and 1.
+    pub brightness: f32,
+}
+
+/// Loads an image from disk and extracts its `count` most dominant colors.
+pub fn extract_palette_from_file(path: &Path, count: usize) -> Result<Palette> {
+    let img = open_image(path)?;
+    Ok(extract_palette(
+        &img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgba8(),
+        count,
+    ))
+}
+
+pub fn extract_palette(img: &RgbaImage, count: usize) -> Palette {
+    let samples: Vec<Lab> = img
+        .pixels()
+        .filter(|p| p[3] >= 128)
+        .map(|p| rgb_to_lab((p[0], p[1], p[2])))
+        .collect();
+
+    if samples.is_empty() || count == 0 {
+        return Palette {
+            colors: Vec::new(),
+            brightness: 0.0,
+        };
+    }
+
+    let brightness = samples.iter().map(|c| c.l).sum::<f32>() / samples.len() as f32 / 100.0;
+
+    let mut centroids = initial_centroids(&samples, count);
+    let mut assignments = vec![0usize; samples.len()];
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (sample, assignment) in samples.iter().zip(assignments.iter_mut()) {
+            let nearest = nearest_centroid(&centroids, *sample);
+            if nearest != *assignment {
+                *assignment = nearest;
+                changed = true;
+            }
+        }
+
+        let mut sums = vec![(0.0f32, 0.0f32, 0.0f32, 0usize); centroids.len()];
+        for (sample, &assignment) in samples.iter().zip(&assignments) {
+            let sum = &mut sums[assignment];
+            sum.0 += sample.l;
+            sum.1 += sample.a;
+            sum.2 += sample.b;
+            sum.3 += 1;
+        }
+        for (centroid, (l, a, b, n)) in centroids.iter_mut().zip(sums) {
+            if n > 0 {
+                let n = n as f32;
+                *centroid = Lab {
+                    l: l / n,
+                    a: a / n,
+                    b: b / n,
+                };
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let mut counts = vec![0usize; centroids.len()];
+    for &assignment in &assignments {
+        counts[assignment] += 1;
+    }
+
+    let mut clusters: Vec<(Lab, usize)> = centroids
+        .into_iter()
+        .zip(counts)
+        .filter(|(_, n)| *n > 0)
+        .collect();
+    clusters.sort_by_key(|c| Reverse(c.1));
+
+    let mut merged: Vec<(Lab, usize)> = Vec::new();
+    for (centroid, n) in clusters {
+        match merged
+            .iter_mut()
+            .find(|(existing, _)| delta_e(*existing, centroid) < MERGE_DISTANCE)
+        {
+            Some((_, total)) => *total += n,
+            None => merged.push((centroid, n)),
+        }
+    }
+
+    merged.sort_by_key(|c| Reverse(c.1));
+
+    let colors = merged
+        .into_iter()
+        .map(|(centroid, n)| PaletteColor {
+            rgb: lab_to_rgb(centroid),
+            weight: n as f32 / samples.len() as f32,
+        })
+        .collect();
+
+    Palette { colors, brightness }
+}
+
+/// k-means++ seeding with a fixed seed so the same image always yields the same palette.
+fn initial_centroids(samples: &[Lab], count: usize) -> Vec<Lab> {
+    let mut rng = StdRng::seed_from_u64(0x5eed);
+    let mut centroids = vec![samples[rng.gen_range(0..samples.len())]];
+    let mut distances = vec![f32::MAX; samples.len()];
+
+    while centroids.len() < count {
+        let last = *centroids.last().unwrap();
+        for (sample, distance) in samples.iter().zip(distances.iter_mut()) {
+            *distance = distance.min(delta_e(*sample, last).powi(2));
+        }
+
+        let total: f32 = distances.iter().sum();
+        if total <= f32::EPSILON {
+            break;
+        }
+
+        let mut target = rng.gen_range(0.0..total);
+        let mut chosen = samples.len() - 1;
+        for (i, distance) in distances.iter().enumerate() {
+            if target < *distance {
+                chosen = i;
+                break;
+            }
+            target -= distance;
+        }
+        centroids.push(samples[chosen]);
+    }
+
+    centroids
+}
+
+fn nearest_centroid(centroids: &[Lab], sample: Lab) -> usize {
+    centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, delta_e(*c, sample)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_solid_image_has_single_color() {
+        let img = RgbaImage::from_pixel(16, 16, Rgba([30, 58, 95, 255]));
+        let palette = extract_palette(&img, 5);
+
+        assert_eq!(palette.colors.len(), 1);
+        let (r, g, b) = palette.colors[0].rgb;
+        assert!((r as i32 - 30).abs() <= 1 && (g as i32 - 58).abs() <= 1);
+        assert!((b as i32 - 95).abs() <= 1);
+        assert!(palette.brightness < 0.3);
+    }
+
+    #[test]
+    fn test_two_tone_image_is_weighted() {
+        let img = RgbaImage::from_fn(10, 10, |x, _| {
+            if x < 7 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([200, 0, 0, 255])
+            }
+        });
+        let palette = extract_palette(&img, 3);
+
+        assert_eq!(palette.colors.len(), 2);
+        assert_eq!(palette.colors[0].rgb, (255, 255, 255));
+        assert!((palette.colors[0].weight - 0.7).abs() < 0.01);
+    }
+}
@@ -0,0 +1,345 @@
+//! How an image is fitted to a screen whose aspect ratio differs from its own.
+
+use clap::ValueEnum;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::color::Rgb;
+
+/// How an image is scaled to the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScaleMode {
+    /// Scale to cover the screen, cropping what does not fit
+    #[default]
+    Fill,
+    /// Scale to fit inside the screen, filling the rest with the background color
+    Fit,
+    /// Show at the original size
+    Center,
+    /// Repeat the image at its original size
+    Tile,
+    /// Scale to the screen size, ignoring the aspect ratio
+    Stretch,
+}
+
+/// Resampling filter for the final resize, from fastest to sharpest. Large downscales are
+/// first halved with a box filter, so the slower filters only work on at most twice the
+/// screen size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScaleFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    #[default]
+    Lanczos3,
+}
+
+impl From<ScaleFilter> for FilterType {
+    fn from(filter: ScaleFilter) -> Self {
+        match filter {
+            ScaleFilter::Nearest => FilterType::Nearest,
+            ScaleFilter::Triangle => FilterType::Triangle,
+            ScaleFilter::CatmullRom => FilterType::CatmullRom,
+            ScaleFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Which part of the image stays visible when it is cropped, or where it sits when it is
+/// smaller than the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    #[default]
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Horizontal and vertical position, from 0 (left/top) to 1 (right/bottom).
+    pub fn position(self) -> (f64, f64) {
+        match self {
+            Self::Center => (0.5, 0.5),
+            Self::Top => (0.5, 0.0),
+            Self::Bottom => (0.5, 1.0),
+            Self::Left => (0.0, 0.5),
+            Self::Right => (1.0, 0.5),
+            Self::TopLeft => (0.0, 0.0),
+            Self::TopRight => (1.0, 0.0),
+            Self::BottomLeft => (0.0, 1.0),
+            Self::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// Scaling mode, anchor, background color and filter for a wallpaper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Placement {
+    pub mode: ScaleMode,
+    pub anchor: Anchor,
+    /// Fills the screen around images that do not cover it
+    pub background: Rgb,
+    pub filter: ScaleFilter,
+    /// Lays one image across all monitors instead of repeating it on each
+    pub span: bool,
+}
+
+/// Renders `image` onto a `width` x `height` screen. The native Wayland backend draws the
+/// wallpaper itself, and Deepin gets a rendered image as it can only fill the screen; the
+/// others pass the placement on to the desktop.
+#[cfg_attr(not(any(feature = "wayland", target_os = "linux")), allow(dead_code))]
+pub fn render(image: &RgbaImage, width: u32, height: u32, placement: &Placement) -> RgbaImage {
+    let (r, g, b) = placement.background;
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+    let (image_width, image_height) = image.dimensions();
+    if image_width == 0 || image_height == 0 || width == 0 || height == 0 {
+        return canvas;
+    }
+
+    let scale_x = width as f64 / image_width as f64;
+    let scale_y = height as f64 / image_height as f64;
+    let size = match placement.mode {
+        ScaleMode::Fill => Some(scaled_size(image, scale_x.max(scale_y), width, height)),
+        ScaleMode::Fit => Some(scaled_size(image, scale_x.min(scale_y), width, height)),
+        ScaleMode::Stretch => Some((width, height)),
+        ScaleMode::Center | ScaleMode::Tile => None,
+    };
+
+    let resized;
+    let image = match size {
+        Some((w, h)) if (w, h) != image.dimensions() => {
+            resized = downscale(image, w, h, placement.filter);
+            &resized
+        }
+        _ => image,
+    };
+
+    if placement.mode == ScaleMode::Tile {
+        for y in (0..height).step_by(image.height() as usize) {
+            for x in (0..width).step_by(image.width() as usize) {
+                blit(&mut canvas, image, x as i64, y as i64);
+            }
+        }
+        return canvas;
+    }
+
+    let (anchor_x, anchor_y) = placement.anchor.position();
+    let x = ((width as f64 - image.width() as f64) * anchor_x).round() as i64;
+    let y = ((height as f64 - image.height() as f64) * anchor_y).round() as i64;
+    blit(&mut canvas, image, x, y);
+    canvas
+}
+
+/// Resizes `image` to `width` x `height`. Images more than twice as large are halved with a
+/// box filter first, which is much cheaper than running the final filter over all of them.
+fn downscale(image: &RgbaImage, width: u32, height: u32, filter: ScaleFilter) -> RgbaImage {
+    let mut halved = None;
+    loop {
+        let current = halved.as_ref().unwrap_or(image);
+        if current.width() / 2 < width || current.height() / 2 < height {
+            break;
+        }
+        halved = Some(halve(current));
+    }
+    let current = halved.as_ref().unwrap_or(image);
+    if current.dimensions() == (width, height) {
+        return halved.unwrap_or_else(|| image.clone());
+    }
+    imageops::resize(current, width, height, filter.into())
+}
+
+/// Averages each 2x2 block of pixels, dropping an odd last row or column. Rows are
+/// processed in parallel.
+fn halve(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = (image.width() / 2, image.height() / 2);
+    let source = image.as_raw();
+    let source_stride = image.width() as usize * 4;
+    let mut data = vec![0; width as usize * height as usize * 4];
+    data.par_chunks_exact_mut(width as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let top = &source[2 * y * source_stride..][..source_stride];
+            let bottom = &source[(2 * y + 1) * source_stride..][..source_stride];
+            for (i, value) in row.iter_mut().enumerate() {
+                // The same channel of the source pixels 2x and 2x + 1.
+                let j = i / 4 * 8 + i % 4;
+                let sum =
+                    top[j] as u16 + top[j + 4] as u16 + bottom[j] as u16 + bottom[j + 4] as u16;
+                *value = ((sum + 2) / 4) as u8;
+            }
+        });
+    RgbaImage::from_raw(width, height, data).expect("buffer matches the image size")
+}
+
+/// Size of the image scaled by `scale`, snapped to the screen size when within a pixel so
+/// rounding never leaves a one-pixel border.
+fn scaled_size(image: &RgbaImage, scale: f64, width: u32, height: u32) -> (u32, u32) {
+    let snap = |size: u32, scale: f64, screen: u32| {
+        let scaled = size as f64 * scale;
+        if (scaled - screen as f64).abs() < 1.0 {
+            screen
+        } else {
+            (scaled.round() as u32).max(1)
+        }
+    };
+    (
+        snap(image.width(), scale, width),
+        snap(image.height(), scale, height),
+    )
+}
+
+/// Copies `image` onto `canvas` with its top-left corner at (`x`, `y`), clipping whatever
+/// falls outside. Transparent pixels are blended over the canvas; opaque rows are copied.
+fn blit(canvas: &mut RgbaImage, image: &RgbaImage, x: i64, y: i64) {
+    let left = x.max(0);
+    let top = y.max(0);
+    let right = (x + image.width() as i64).min(canvas.width() as i64);
+    let bottom = (y + image.height() as i64).min(canvas.height() as i64);
+    if left >= right || top >= bottom {
+        return;
+    }
+
+    let canvas_stride = canvas.width() as usize * 4;
+    let image_stride = image.width() as usize * 4;
+    let source = image.as_raw();
+    let span = (right - left) as usize * 4;
+    let canvas: &mut [u8] = canvas;
+    let rows = canvas[top as usize * canvas_stride..bottom as usize * canvas_stride]
+        .par_chunks_exact_mut(canvas_stride);
+    rows.enumerate().for_each(|(row, target)| {
+        let sy = (top - y) as usize + row;
+        let sx = (left - x) as usize * 4;
+        let source = &source[sy * image_stride + sx..][..span];
+        let target = &mut target[left as usize * 4..][..span];
+        if source.chunks_exact(4).all(|pixel| pixel[3] == 255) {
+            target.copy_from_slice(source);
+            return;
+        }
+        for (target, source) in target.chunks_exact_mut(4).zip(source.chunks_exact(4)) {
+            let alpha = source[3] as u32;
+            for channel in 0..3 {
+                target[channel] = ((source[channel] as u32 * alpha
+                    + target[channel] as u32 * (255 - alpha))
+                    / 255) as u8;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x2 image: left half red, right half blue.
+    fn halves() -> RgbaImage {
+        RgbaImage::from_fn(4, 2, |x, _| {
+            if x < 2 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 255])
+            }
+        })
+    }
+
+    fn placement(mode: ScaleMode, anchor: Anchor) -> Placement {
+        Placement {
+            mode,
+            anchor,
+            background: (0, 255, 0),
+            filter: ScaleFilter::default(),
+            span: false,
+        }
+    }
+
+    #[test]
+    fn test_fill_crops_at_anchor() {
+        // Scaled to 8x4 to cover 2x4, the anchor picks which half stays visible.
+        let left = render(&halves(), 2, 4, &placement(ScaleMode::Fill, Anchor::Left));
+        assert_eq!(left.dimensions(), (2, 4));
+        assert!(left.pixels().all(|p| p[0] > 200 && p[2] < 50));
+
+        let right = render(&halves(), 2, 4, &placement(ScaleMode::Fill, Anchor::Right));
+        assert!(right.pixels().all(|p| p[2] > 200 && p[0] < 50));
+    }
+
+    #[test]
+    fn test_fit_letterboxes_with_background() {
+        let fitted = render(&halves(), 4, 6, &placement(ScaleMode::Fit, Anchor::Center));
+        assert_eq!(fitted.dimensions(), (4, 6));
+        // 4x2 fits as is, with two rows of background above and below.
+        assert_eq!(fitted.get_pixel(0, 0), &Rgba([0, 255, 0, 255]));
+        assert_eq!(fitted.get_pixel(0, 5), &Rgba([0, 255, 0, 255]));
+        assert_eq!(fitted.get_pixel(0, 2), &Rgba([255, 0, 0, 255]));
+        assert_eq!(fitted.get_pixel(3, 3), &Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_center_and_tile_keep_size() {
+        let centered = render(
+            &halves(),
+            6,
+            2,
+            &placement(ScaleMode::Center, Anchor::Center),
+        );
+        assert_eq!(centered.get_pixel(0, 0), &Rgba([0, 255, 0, 255]));
+        assert_eq!(centered.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(centered.get_pixel(5, 0), &Rgba([0, 255, 0, 255]));
+
+        let tiled = render(&halves(), 8, 4, &placement(ScaleMode::Tile, Anchor::Center));
+        assert_eq!(tiled.get_pixel(4, 2), &Rgba([255, 0, 0, 255]));
+        assert_eq!(tiled.get_pixel(7, 3), &Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_downscale_halves_large_images() {
+        let large = RgbaImage::from_fn(64, 32, |x, _| {
+            if x < 32 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 255])
+            }
+        });
+        for filter in ScaleFilter::value_variants() {
+            let small = downscale(&large, 6, 3, *filter);
+            assert_eq!(small.dimensions(), (6, 3));
+            assert_eq!(
+                small.get_pixel(0, 1),
+                &Rgba([255, 0, 0, 255]),
+                "{:?}",
+                filter
+            );
+            assert_eq!(
+                small.get_pixel(5, 1),
+                &Rgba([0, 0, 255, 255]),
+                "{:?}",
+                filter
+            );
+        }
+        assert_eq!(halve(&large).get_pixel(15, 0), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_stretch_ignores_aspect_ratio() {
+        let stretched = render(
+            &halves(),
+            2,
+            8,
+            &placement(ScaleMode::Stretch, Anchor::Center),
+        );
+        assert_eq!(stretched.dimensions(), (2, 8));
+        assert!(stretched.get_pixel(0, 4)[0] > 200);
+        assert!(stretched.get_pixel(1, 4)[2] > 200);
+    }
+}
@@ -0,0 +1,877 @@
+//! Slideshow playlist ordering and the slideshow loop.
+
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep_until, Instant};
+
+use super::control::{Control, ControlSignals, ControlSocket, Request, Response, Status};
+use super::watcher::SourceWatcher;
+use super::{Placement, Transition};
+use crate::{
+    engine,
+    errors::{Result, WallrusError},
+    library::{unix_now, ColorQuery, Library, MAX_RATING},
+    utils::{scan_images, state_dir, write_atomic, ScanOptions},
+};
+
+const STATE_FILE: &str = "slideshow.json";
+
+/// Number of shown images remembered for stepping backwards.
+const HISTORY_LIMIT: usize = 100;
+
+/// Lower bound for jittered intervals.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Staleness assumed for images that have never been shown (30 days).
+const NEVER_SHOWN_AGE: u64 = 30 * 24 * 60 * 60;
+
+/// The order in which slideshow images are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Order {
+    /// In sorted order, wrapping around at the end
+    #[default]
+    Sequential,
+    /// Every image once per cycle, reshuffled each cycle
+    Shuffle,
+    /// Uniformly random, avoiding the last few images shown
+    RandomNoRepeat,
+    /// Random, favoring highly rated or long-unseen images
+    Weighted,
+}
+
+/// Sort key for sequential slideshows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    /// By file path
+    #[default]
+    Name,
+    /// By modification time, oldest first
+    Mtime,
+}
+
+/// What the weighted order favors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WeightBy {
+    /// Higher library ratings are shown more often
+    Rating,
+    /// Images not shown for a long time are shown more often
+    #[default]
+    Staleness,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlideshowOptions {
+    pub sources: Vec<PathBuf>,
+    pub scan: ScanOptions,
+    pub interval: Duration,
+    /// Each interval is shifted by a random amount of up to this much either way.
+    pub jitter: Duration,
+    pub query: ColorQuery,
+    pub order: Order,
+    pub sort: SortKey,
+    /// Number of recently shown images to avoid in the random orders.
+    pub no_repeat: usize,
+    pub weight_by: WeightBy,
+    pub placement: Placement,
+    pub transition: Transition,
+}
+
+/// Slideshow position persisted across restarts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SlideshowState {
+    pub current: Option<PathBuf>,
+    /// Recently shown images, most recent last.
+    pub recent: Vec<PathBuf>,
+}
+
+impl SlideshowState {
+    pub fn load() -> Result<Self> {
+        let path = state_dir()?.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(&path)?;
+        // A corrupt state file only costs us the position, so start over rather than fail.
+        Ok(serde_json::from_str(&data).unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).map_err(|e| {
+            WallrusError::Config(format!("Failed to serialize slideshow state: {}", e))
+        })?;
+        write_atomic(&state_dir()?.join(STATE_FILE), data.as_bytes())
+    }
+}
+
+pub struct Playlist {
+    images: Vec<PathBuf>,
+    order: Order,
+    no_repeat: usize,
+    /// Index of the next image in `images` (sequential) or `queue` (shuffle).
+    cursor: usize,
+    queue: Vec<PathBuf>,
+    current: Option<PathBuf>,
+    /// The last `no_repeat` images shown, most recent last.
+    recent: VecDeque<PathBuf>,
+    /// Images shown so far, for stepping backwards; most recent last.
+    history: Vec<PathBuf>,
+    /// How many steps back from the end of `history` the current image is.
+    back: usize,
+}
+
+impl Playlist {
+    pub fn new(images: Vec<PathBuf>, order: Order, no_repeat: usize) -> Self {
+        Self {
+            images,
+            order,
+            no_repeat,
+            cursor: 0,
+            queue: Vec::new(),
+            current: None,
+            recent: VecDeque::new(),
+            history: Vec::new(),
+            back: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Continues from a previously saved position.
+    pub fn resume(&mut self, state: &SlideshowState) {
+        self.recent = state
+            .recent
+            .iter()
+            .filter(|path| self.images.contains(path))
+            .cloned()
+            .collect();
+        self.trim_recent();
+
+        if let Some(current) = &state.current {
+            if let Some(index) = self.images.iter().position(|path| path == current) {
+                self.cursor = index + 1;
+                self.current = Some(current.clone());
+            }
+        }
+    }
+
+    /// Replaces the image list after the sources changed on disk, keeping the position within
+    /// the current cycle.
+    pub fn update(&mut self, images: Vec<PathBuf>) {
+        let new: HashSet<&PathBuf> = images.iter().collect();
+        let old: HashSet<&PathBuf> = self.images.iter().collect();
+
+        // Shuffle: drop vanished images from the queue and append new ones to this cycle.
+        let played = self.cursor.min(self.queue.len());
+        let removed_before_cursor = self.queue[..played]
+            .iter()
+            .filter(|path| !new.contains(path))
+            .count();
+        let added: Vec<PathBuf> = images
+            .iter()
+            .filter(|path| !old.contains(path))
+            .cloned()
+            .collect();
+        if !self.queue.is_empty() {
+            self.queue.retain(|path| new.contains(path));
+            self.queue.extend(added);
+        }
+
+        self.recent.retain(|path| new.contains(path));
+        self.history.retain(|path| new.contains(path));
+        self.back = self.back.min(self.history.len().saturating_sub(1));
+        if self.order == Order::Shuffle {
+            self.cursor -= removed_before_cursor;
+        } else if let Some(index) = self
+            .current
+            .as_ref()
+            .and_then(|current| images.iter().position(|path| path == current))
+        {
+            self.cursor = index + 1;
+        }
+
+        self.images = images;
+        self.trim_recent();
+    }
+
+    /// Drops a single image, e.g. one that was deleted before the watcher noticed.
+    pub fn remove(&mut self, image: &Path) {
+        let images = self
+            .images
+            .iter()
+            .filter(|path| path.as_path() != image)
+            .cloned()
+            .collect();
+        self.update(images);
+    }
+
+    pub fn state(&self) -> SlideshowState {
+        SlideshowState {
+            current: self.current.clone(),
+            recent: self.recent.iter().cloned().collect(),
+        }
+    }
+
+    /// Picks the next image. `weight` is only consulted by `Order::Weighted` and must return a
+    /// positive value.
+    pub fn next<R: Rng>(&mut self, rng: &mut R, weight: impl Fn(&Path) -> f64) -> Option<PathBuf> {
+        if self.images.is_empty() {
+            return None;
+        }
+
+        // After stepping back, walk forward through the history first.
+        if self.back > 0 {
+            self.back -= 1;
+            return Some(self.history_current());
+        }
+
+        let next = match self.order {
+            Order::Sequential => {
+                let image = self.images[self.cursor % self.images.len()].clone();
+                self.cursor = (self.cursor % self.images.len()) + 1;
+                image
+            }
+            Order::Shuffle => {
+                if self.cursor >= self.queue.len() {
+                    self.reshuffle(rng);
+                }
+                self.cursor += 1;
+                self.queue[self.cursor - 1].clone()
+            }
+            Order::RandomNoRepeat => self.candidates().choose(rng).copied()?.clone(),
+            Order::Weighted => {
+                let candidates = self.candidates();
+                let weights: Vec<f64> = candidates.iter().map(|path| weight(path)).collect();
+                match WeightedIndex::new(&weights) {
+                    Ok(distribution) => candidates[distribution.sample(rng)].clone(),
+                    Err(_) => candidates.choose(rng).copied()?.clone(),
+                }
+            }
+        };
+
+        self.current = Some(next.clone());
+        self.recent.push_back(next.clone());
+        self.trim_recent();
+        self.history.push(next.clone());
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        Some(next)
+    }
+
+    /// Steps back to the image shown before the current one, if there is one.
+    pub fn previous(&mut self) -> Option<PathBuf> {
+        if self.back + 1 >= self.history.len() {
+            return None;
+        }
+        self.back += 1;
+        Some(self.history_current())
+    }
+
+    fn history_current(&mut self) -> PathBuf {
+        let image = self.history[self.history.len() - 1 - self.back].clone();
+        self.current = Some(image.clone());
+        image
+    }
+
+    fn reshuffle<R: Rng>(&mut self, rng: &mut R) {
+        self.queue = self.images.clone();
+        self.queue.shuffle(rng);
+        // Avoid showing the same image twice in a row across a cycle boundary.
+        if self.queue.len() > 1 && self.queue.first() == self.current.as_ref() {
+            let last = self.queue.len() - 1;
+            self.queue.swap(0, last);
+        }
+        self.cursor = 0;
+    }
+
+    /// Images not shown recently. Falls back to all images if everything was shown recently.
+    fn candidates(&self) -> Vec<&PathBuf> {
+        let fresh: Vec<&PathBuf> = self
+            .images
+            .iter()
+            .filter(|path| !self.recent.contains(path))
+            .collect();
+        if fresh.is_empty() {
+            self.images.iter().collect()
+        } else {
+            fresh
+        }
+    }
+
+    fn trim_recent(&mut self) {
+        // Never avoid every image, or the random orders would have nothing left to pick.
+        let limit = self.no_repeat.min(self.images.len().saturating_sub(1));
+        while self.recent.len() > limit {
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// Weight of an image in the weighted order, always positive.
+fn image_weight(library: &Library, image: &Path, weight_by: WeightBy) -> f64 {
+    let stats = library.stats(image);
+    match weight_by {
+        WeightBy::Rating => {
+            // Unrated images sit in the middle of the scale.
+            let rating = stats
+                .rating
+                .map(f64::from)
+                .unwrap_or(MAX_RATING as f64 / 2.0);
+            rating + 0.5
+        }
+        WeightBy::Staleness => {
+            let age = stats
+                .last_shown
+                .map(|shown| unix_now().saturating_sub(shown))
+                .unwrap_or(NEVER_SHOWN_AGE)
+                .min(NEVER_SHOWN_AGE);
+            1.0 + age as f64 / 3600.0
+        }
+    }
+}
+
+fn sort_images(images: &mut [PathBuf], sort: SortKey) {
+    match sort {
+        SortKey::Name => images.sort(),
+        SortKey::Mtime => images.sort_by_cached_key(|path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .unwrap_or(std::time::UNIX_EPOCH)
+        }),
+    }
+}
+
+/// Scans the sources and applies the color filter and sort order. A source that is missing
+/// (removed, or on a drive that is not mounted) counts as empty until it comes back.
+fn load_images(options: &SlideshowOptions, library: &mut Library) -> Result<Vec<PathBuf>> {
+    let sources: Vec<PathBuf> = options
+        .sources
+        .iter()
+        .filter(|source| {
+            let exists = source.exists();
+            if !exists {
+                eprintln!("Warning: {:?} is missing, treating it as empty", source);
+            }
+            exists
+        })
+        .cloned()
+        .collect();
+    let images = scan_images(&sources, &options.scan)?;
+    let mut images = library.filter(&images, &options.query)?;
+    sort_images(&mut images, options.sort);
+    Ok(images)
+}
+
+fn rescan(
+    options: &SlideshowOptions,
+    library: &mut Library,
+    playlist: &mut Playlist,
+) -> Result<()> {
+    let before = playlist.len();
+    playlist.update(load_images(options, library)?);
+    if playlist.len() != before {
+        println!(
+            "Sources changed: {} images (was {})",
+            playlist.len(),
+            before
+        );
+    }
+    Ok(())
+}
+
+/// Picks the next delay, applying the configured jitter.
+fn next_delay<R: Rng>(options: &SlideshowOptions, rng: &mut R) -> Duration {
+    if options.jitter.is_zero() {
+        return options.interval;
+    }
+    let jitter = options.jitter.as_secs_f64();
+    let delay = options.interval.as_secs_f64() + rng.gen_range(-jitter..=jitter);
+    Duration::from_secs_f64(delay.max(MIN_INTERVAL.as_secs_f64()))
+}
+
+/// Which way the slideshow should move.
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Next,
+    Previous,
+}
+
+/// The state of a running slideshow.
+struct Slideshow {
+    options: SlideshowOptions,
+    library: Library,
+    playlist: Playlist,
+    watcher: SourceWatcher,
+    rng: StdRng,
+    /// When the next change is due; `None` while paused or without images.
+    deadline: Option<Instant>,
+    paused: bool,
+    /// Time that was left until the next change when the slideshow was paused.
+    remaining: Option<Duration>,
+}
+
+impl Slideshow {
+    fn new(options: SlideshowOptions) -> Result<Self> {
+        let mut library = Library::load()?;
+        let watcher = SourceWatcher::new(&options.sources, options.scan.recursive)?;
+        let mut playlist = Playlist::new(
+            load_images(&options, &mut library)?,
+            options.order,
+            options.no_repeat,
+        );
+        playlist.resume(&SlideshowState::load()?);
+
+        Ok(Self {
+            options,
+            library,
+            playlist,
+            watcher,
+            rng: StdRng::from_entropy(),
+            deadline: None,
+            paused: false,
+            remaining: None,
+        })
+    }
+
+    /// Shows the next or previous image, skipping files that have disappeared or fail to
+    /// set. The next change is scheduled even when no image could be set.
+    async fn step(&mut self, direction: Step) -> Result<()> {
+        let (library, weight_by, rng) = (&self.library, self.options.weight_by, &mut self.rng);
+        let (placement, transition) = (self.options.placement, self.options.transition);
+        let shown = show_next(
+            &mut self.playlist,
+            |playlist| match direction {
+                Step::Next => playlist.next(rng, |path| image_weight(library, path, weight_by)),
+                Step::Previous => playlist.previous(),
+            },
+            |image| show(image, placement, transition),
+        )
+        .await;
+
+        let image = match shown {
+            Ok(Some(image)) => image,
+            Ok(None) if self.playlist.is_empty() => {
+                println!("No images available, waiting for new files...");
+                self.deadline = None;
+                return Ok(());
+            }
+            Ok(None) => {
+                return Err(WallrusError::Config(
+                    "No earlier image in this session".into(),
+                ))
+            }
+            Err(e) => {
+                self.schedule();
+                return Err(e);
+            }
+        };
+        self.schedule();
+        self.library.record_shown(&image)?;
+        self.library.save()?;
+        self.playlist.state().save()
+    }
+
+    /// Starts a new interval, unless paused.
+    fn schedule(&mut self) {
+        self.remaining = None;
+        self.deadline = if self.paused {
+            None
+        } else {
+            Some(Instant::now() + next_delay(&self.options, &mut self.rng))
+        };
+    }
+
+    fn pause(&mut self) {
+        if !self.paused {
+            self.paused = true;
+            self.remaining = self
+                .deadline
+                .take()
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            match self.remaining.take() {
+                Some(remaining) => self.deadline = Some(Instant::now() + remaining),
+                None => self.schedule(),
+            }
+        }
+    }
+
+    fn set_interval(&mut self, seconds: u64) -> Result<()> {
+        if seconds < MIN_INTERVAL.as_secs() {
+            return Err(WallrusError::Config(format!(
+                "Interval must be at least {} second",
+                MIN_INTERVAL.as_secs()
+            )));
+        }
+        self.options.interval = Duration::from_secs(seconds);
+        if self.deadline.is_some() || self.remaining.is_some() {
+            self.schedule();
+        }
+        println!("Interval set to {}s", seconds);
+        Ok(())
+    }
+
+    fn favorite_current(&mut self) -> Result<()> {
+        let current = self
+            .playlist
+            .current
+            .clone()
+            .ok_or_else(|| WallrusError::Config("No wallpaper is being shown".into()))?;
+        self.library.set_rating(&current, MAX_RATING)?;
+        self.library.save()?;
+        println!("Rated {:?} {}/{}", current, MAX_RATING, MAX_RATING);
+        Ok(())
+    }
+
+    /// Rescans the sources. Returns whether images became available again.
+    fn rescan(&mut self) -> Result<bool> {
+        let was_empty = self.playlist.is_empty();
+        self.watcher.refresh()?;
+        rescan(&self.options, &mut self.library, &mut self.playlist)?;
+        Ok(was_empty && !self.playlist.is_empty())
+    }
+
+    /// Switches to new options, keeping the position. Returns whether to show an image now.
+    fn reload(&mut self, options: SlideshowOptions) -> Result<bool> {
+        println!("Reloading configuration");
+        let images = load_images(&options, &mut self.library)?;
+        self.watcher = SourceWatcher::new(&options.sources, options.scan.recursive)?;
+        let state = self.playlist.state();
+        self.playlist = Playlist::new(images, options.order, options.no_repeat);
+        self.playlist.resume(&state);
+        self.options = options;
+        Ok(self.deadline.is_none() && !self.paused)
+    }
+
+    fn status(&self) -> Status {
+        Status {
+            current: self.playlist.current.clone(),
+            paused: self.paused,
+            images: self.playlist.len(),
+            interval: self.options.interval.as_secs(),
+            next_change: self
+                .deadline
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()).as_secs()),
+        }
+    }
+}
+
+/// Runs a slideshow from images in the given source directories, changing wallpaper at a
+/// given interval. Only images matching the color query are shown.
+///
+/// The sources are watched: new images join the playlist, deleted ones are skipped, and the
+/// slideshow pauses while no images are available, including while a source directory is
+/// missing. An image that fails to set is skipped with a warning rather than ending the
+/// slideshow. Signals and the control socket can skip
+/// forward and back, pause, change the interval, stop the slideshow (saving its position),
+/// or call `reload` to re-read the options and rescan the sources.
+pub async fn run_slideshow(
+    options: SlideshowOptions,
+    reload: impl Fn() -> Result<SlideshowOptions>,
+) -> Result<()> {
+    let mut signals = ControlSignals::new()?;
+    let mut socket = ControlSocket::bind()?;
+    let mut slideshow = Slideshow::new(options)?;
+
+    println!(
+        "Starting slideshow with {} images",
+        slideshow.playlist.len()
+    );
+    // A failed change is reported and the slideshow carries on with the next image.
+    let warn = |result: Result<()>| {
+        if let Err(e) = result {
+            eprintln!("Warning: {}", e);
+        }
+    };
+    warn(slideshow.step(Step::Next).await);
+
+    loop {
+        let deadline = slideshow.deadline;
+        let request = tokio::select! {
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                warn(slideshow.step(Step::Next).await);
+                continue;
+            }
+            _ = slideshow.watcher.changed() => {
+                match slideshow.rescan() {
+                    Ok(true) => warn(slideshow.step(Step::Next).await),
+                    Ok(false) => {}
+                    Err(e) => warn(Err(e)),
+                }
+                continue;
+            }
+            control = signals.recv() => Request::from(control),
+            request = socket.recv() => request,
+        };
+
+        let result = match request.control {
+            Control::Shutdown => {
+                slideshow.playlist.state().save()?;
+                request.reply(Response::new(Ok(()), slideshow.status()));
+                println!("Slideshow stopped");
+                return Ok(());
+            }
+            Control::Reload => match reload().and_then(|options| slideshow.reload(options)) {
+                Ok(true) => slideshow.step(Step::Next).await,
+                Ok(false) => Ok(()),
+                Err(e) => Err(e),
+            },
+            Control::Next => slideshow.step(Step::Next).await,
+            Control::Previous => slideshow.step(Step::Previous).await,
+            Control::Pause => {
+                slideshow.pause();
+                Ok(())
+            }
+            Control::Resume => {
+                slideshow.resume();
+                Ok(())
+            }
+            Control::SetInterval { seconds } => slideshow.set_interval(seconds),
+            Control::Status => Ok(()),
+            Control::FavoriteCurrent => slideshow.favorite_current(),
+        };
+
+        if let Err(e) = &result {
+            eprintln!("Warning: {:?} failed: {}", request.control, e);
+        }
+        request.reply(Response::new(result, slideshow.status()));
+    }
+}
+
+/// Takes images from the playlist with `pick` until `show` sets one, and returns it; `None`
+/// when `pick` has nothing. Files that have disappeared are dropped from the playlist, and
+/// images that fail to set (one that does not decode, a backend error) are skipped with a
+/// warning. After as many failures as there are images, the last error is returned instead,
+/// so a broken backend does not spin through the whole library.
+async fn show_next<Show, Shown>(
+    playlist: &mut Playlist,
+    mut pick: impl FnMut(&mut Playlist) -> Option<PathBuf>,
+    mut show: Show,
+) -> Result<Option<PathBuf>>
+where
+    Show: FnMut(PathBuf) -> Shown,
+    Shown: std::future::Future<Output = Result<()>>,
+{
+    let mut failures = 0;
+    while let Some(image) = pick(playlist) {
+        if !image.exists() {
+            eprintln!("Warning: {:?} no longer exists, skipping", image);
+            playlist.remove(&image);
+            continue;
+        }
+        match show(image.clone()).await {
+            Ok(()) => return Ok(Some(image)),
+            Err(e) => {
+                failures += 1;
+                if failures >= playlist.len().max(1) {
+                    return Err(e);
+                }
+                eprintln!("Warning: could not set {:?}, skipping: {}", image, e);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Sets the wallpaper without blocking the runtime.
+async fn show(image: PathBuf, placement: Placement, transition: Transition) -> Result<()> {
+    println!("Setting wallpaper: {:?}", image);
+    tokio::task::spawn_blocking(move || engine::set_wallpaper(&image, &placement, &transition))
+        .await
+        .map_err(|e| WallrusError::Config(format!("Wallpaper task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    fn images(n: usize) -> Vec<PathBuf> {
+        (0..n)
+            .map(|i| PathBuf::from(format!("{}.png", i)))
+            .collect()
+    }
+
+    fn take(playlist: &mut Playlist, rng: &mut StdRng, n: usize) -> Vec<PathBuf> {
+        (0..n)
+            .map(|_| playlist.next(rng, |_| 1.0).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sequential_resumes_after_current() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut playlist = Playlist::new(images(4), Order::Sequential, 2);
+        playlist.resume(&SlideshowState {
+            current: Some(PathBuf::from("2.png")),
+            recent: vec![PathBuf::from("2.png")],
+        });
+
+        assert_eq!(
+            take(&mut playlist, &mut rng, 3),
+            vec![
+                PathBuf::from("3.png"),
+                PathBuf::from("0.png"),
+                PathBuf::from("1.png")
+            ]
+        );
+        assert_eq!(playlist.state().current, Some(PathBuf::from("1.png")));
+    }
+
+    #[test]
+    fn test_shuffle_shows_every_image_each_cycle() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut playlist = Playlist::new(images(5), Order::Shuffle, 1);
+
+        for _ in 0..3 {
+            let mut cycle = take(&mut playlist, &mut rng, 5);
+            cycle.sort();
+            assert_eq!(cycle, images(5));
+        }
+    }
+
+    #[test]
+    fn test_random_no_repeat_avoids_recent() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut playlist = Playlist::new(images(4), Order::RandomNoRepeat, 3);
+        let shown = take(&mut playlist, &mut rng, 40);
+
+        for window in shown.windows(4) {
+            let mut unique = window.to_vec();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), 4, "repeat within window: {:?}", window);
+        }
+    }
+
+    #[test]
+    fn test_previous_walks_back_then_forward() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut playlist = Playlist::new(images(5), Order::Shuffle, 0);
+        let shown = take(&mut playlist, &mut rng, 3);
+
+        assert_eq!(playlist.previous(), Some(shown[1].clone()));
+        assert_eq!(playlist.previous(), Some(shown[0].clone()));
+        assert_eq!(playlist.previous(), None);
+        assert_eq!(playlist.state().current, Some(shown[0].clone()));
+
+        assert_eq!(take(&mut playlist, &mut rng, 2), shown[1..].to_vec());
+        assert!(!shown.contains(&take(&mut playlist, &mut rng, 1)[0]));
+    }
+
+    #[test]
+    fn test_update_keeps_sequential_position() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut playlist = Playlist::new(images(3), Order::Sequential, 0);
+        take(&mut playlist, &mut rng, 2);
+
+        // "0.png" was deleted and "1b.png" appeared right after the current image.
+        playlist.update(vec![
+            PathBuf::from("1.png"),
+            PathBuf::from("1b.png"),
+            PathBuf::from("2.png"),
+        ]);
+        assert_eq!(
+            take(&mut playlist, &mut rng, 3),
+            vec![
+                PathBuf::from("1b.png"),
+                PathBuf::from("2.png"),
+                PathBuf::from("1.png")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_shuffle_cycle_follows_changes() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let mut playlist = Playlist::new(images(4), Order::Shuffle, 0);
+        let shown = take(&mut playlist, &mut rng, 2);
+
+        let mut remaining: Vec<PathBuf> = images(4)
+            .into_iter()
+            .filter(|path| !shown.contains(path))
+            .collect();
+        let removed = remaining.pop().unwrap();
+        let mut updated: Vec<PathBuf> = images(4).into_iter().filter(|p| *p != removed).collect();
+        updated.push(PathBuf::from("new.png"));
+        playlist.update(updated);
+
+        let mut rest = take(&mut playlist, &mut rng, 2);
+        rest.sort();
+        remaining.push(PathBuf::from("new.png"));
+        remaining.sort();
+        assert_eq!(rest, remaining);
+    }
+
+    #[test]
+    fn test_weighted_prefers_heavier_images() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut playlist = Playlist::new(images(2), Order::Weighted, 0);
+        let heavy = PathBuf::from("0.png");
+
+        let hits = (0..200)
+            .filter(|_| {
+                playlist
+                    .next(&mut rng, |path| if path == heavy { 9.0 } else { 1.0 })
+                    .unwrap()
+                    == heavy
+            })
+            .count();
+        assert!(hits > 150, "heavy image chosen {} times", hits);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_image_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = (0..3)
+            .map(|i| dir.path().join(format!("{}.png", i)))
+            .collect();
+        for path in [&paths[0], &paths[2]] {
+            image::RgbaImage::new(1, 1).save(path).unwrap();
+        }
+        fs::write(&paths[1], b"not a png").unwrap();
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut playlist = Playlist::new(paths.clone(), Order::Sequential, 0);
+        let decode = |image: PathBuf| async move { crate::utils::open_image(&image).map(|_| ()) };
+        let mut shown = Vec::new();
+        for _ in 0..3 {
+            let image = show_next(&mut playlist, |p| p.next(&mut rng, |_| 1.0), decode)
+                .await
+                .unwrap();
+            shown.push(image.unwrap());
+        }
+        assert_eq!(shown, [&paths[0], &paths[2], &paths[0]].map(PathBuf::clone));
+        // The corrupt image stays in the playlist in case it is fixed.
+        assert_eq!(playlist.len(), 3);
+
+        // With nothing that can be set, the error comes back instead of looping.
+        let mut broken = Playlist::new(vec![paths[1].clone()], Order::Sequential, 0);
+        assert!(
+            show_next(&mut broken, |p| p.next(&mut rng, |_| 1.0), decode)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Animated transitions between two wallpapers, drawn frame by frame by the native Wayland
+//! backend. Without it only the settings are used.
+#![cfg_attr(not(feature = "wayland"), allow(dead_code))]
+
+use std::fmt;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::Anchor;
+
+/// How the new image replaces the old one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransitionKind {
+    /// Switch at once
+    #[default]
+    None,
+    /// Crossfade
+    Fade,
+    /// Sweep across the screen, see `--transition-angle`
+    Wipe,
+    /// Grow a circle, see `--transition-position`
+    Grow,
+    /// Pixelate the old image, then sharpen the new one
+    Pixelate,
+}
+
+/// How progress is spread over the duration of a transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps linear progress from 0 to 1 onto the curve (cubic for the eased variants).
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t * t,
+            Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Self::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Self::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+        }
+    }
+}
+
+/// A point on the screen as fractions of its width and height. Written as an anchor name
+/// (`center`, `top-left`, ...) or as `x,y`, e.g. `0.25,0.75`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Default for Point {
+    fn default() -> Self {
+        Self { x: 0.5, y: 0.5 }
+    }
+}
+
+impl FromStr for Point {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(anchor) = Anchor::from_str(s, true) {
+            let (x, y) = anchor.position();
+            return Ok(Self { x, y });
+        }
+        let invalid = || {
+            format!(
+                "Invalid position {:?}, expected an anchor name or \"x,y\" between 0 and 1",
+                s
+            )
+        };
+        let (x, y) = s.split_once(',').ok_or_else(invalid)?;
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|v| (0.0..=1.0).contains(v))
+                .ok_or_else(invalid)
+        };
+        Ok(Self {
+            x: parse(x)?,
+            y: parse(y)?,
+        })
+    }
+}
+
+impl TryFrom<String> for Point {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Point> for String {
+    fn from(value: Point) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
+/// A transition and its parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    /// Length in seconds
+    pub duration: f64,
+    pub easing: Easing,
+    /// Direction of `wipe` in degrees: 0 sweeps left to right, 90 top to bottom
+    pub angle: f64,
+    /// Center of `grow`
+    pub position: Point,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self {
+            kind: TransitionKind::None,
+            duration: 1.0,
+            easing: Easing::default(),
+            angle: 0.0,
+            position: Point::default(),
+        }
+    }
+}
+
+impl Transition {
+    /// Whether there is anything to animate.
+    pub fn is_animated(&self) -> bool {
+        self.kind != TransitionKind::None && self.duration > 0.0
+    }
+}
+
+/// Draws the frame at `progress` (from 0 to 1, before easing) of a transition from `from`
+/// to `to` into `out`. All three are `width` x `height` images with four bytes per pixel;
+/// the channel order does not matter.
+pub fn blend(
+    transition: &Transition,
+    progress: f64,
+    from: &[u8],
+    to: &[u8],
+    out: &mut [u8],
+    width: u32,
+    height: u32,
+) {
+    let p = transition.easing.apply(progress);
+    let (w, h) = (width as usize, height as usize);
+
+    match transition.kind {
+        TransitionKind::None => out.copy_from_slice(to),
+        TransitionKind::Fade => {
+            let weight = (p * 256.0).round() as u32;
+            for ((o, &f), &t) in out.iter_mut().zip(from).zip(to) {
+                *o = ((f as u32 * (256 - weight) + t as u32 * weight) >> 8) as u8;
+            }
+        }
+        TransitionKind::Wipe => {
+            let (dy, dx) = transition.angle.to_radians().sin_cos();
+            // The edge sweeps from the corner furthest back to the one furthest ahead.
+            let corners = [
+                0.0,
+                w as f64 * dx,
+                h as f64 * dy,
+                w as f64 * dx + h as f64 * dy,
+            ];
+            let start = corners.iter().copied().fold(f64::INFINITY, f64::min);
+            let end = corners.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let edge = start + (end - start) * p;
+            copy_where(from, to, out, w, h, |x, y| {
+                (x as f64 + 0.5) * dx + (y as f64 + 0.5) * dy < edge
+            });
+        }
+        TransitionKind::Grow => {
+            let cx = transition.position.x * w as f64;
+            let cy = transition.position.y * h as f64;
+            let reach = |x: f64, y: f64| (x - cx).hypot(y - cy);
+            let furthest = reach(0.0, 0.0)
+                .max(reach(w as f64, 0.0))
+                .max(reach(0.0, h as f64))
+                .max(reach(w as f64, h as f64));
+            let radius = furthest * p;
+            copy_where(from, to, out, w, h, |x, y| {
+                reach(x as f64 + 0.5, y as f64 + 0.5) <= radius
+            });
+        }
+        TransitionKind::Pixelate => {
+            // Blocks grow to 1/32 of the screen halfway through, when the images swap.
+            let largest = (w.max(h) / 32).max(1) as f64;
+            let block = 1 + (largest * (1.0 - (2.0 * p - 1.0).abs())).round() as usize;
+            let source = if p < 0.5 { from } else { to };
+            for y in 0..h {
+                let sy = y - y % block;
+                for x in 0..w {
+                    let sx = x - x % block;
+                    let (i, s) = ((y * w + x) * 4, (sy * w + sx) * 4);
+                    out[i..i + 4].copy_from_slice(&source[s..s + 4]);
+                }
+            }
+        }
+    }
+}
+
+/// Fills `out` with `to` where `covered` holds and with `from` elsewhere.
+fn copy_where(
+    from: &[u8],
+    to: &[u8],
+    out: &mut [u8],
+    width: usize,
+    height: usize,
+    covered: impl Fn(usize, usize) -> bool,
+) {
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) * 4;
+            let source = if covered(x, y) { to } else { from };
+            out[i..i + 4].copy_from_slice(&source[i..i + 4]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 4;
+    const HEIGHT: u32 = 4;
+
+    fn frame(transition: TransitionKind, progress: f64) -> Vec<u8> {
+        let transition = Transition {
+            kind: transition,
+            easing: Easing::Linear,
+            ..Default::default()
+        };
+        let from = vec![0; (WIDTH * HEIGHT * 4) as usize];
+        let to = vec![200; (WIDTH * HEIGHT * 4) as usize];
+        let mut out = vec![1; (WIDTH * HEIGHT * 4) as usize];
+        blend(&transition, progress, &from, &to, &mut out, WIDTH, HEIGHT);
+        out
+    }
+
+    fn pixel(frame: &[u8], x: u32, y: u32) -> u8 {
+        frame[((y * WIDTH + x) * 4) as usize]
+    }
+
+    #[test]
+    fn test_transitions_start_at_old_and_end_at_new() {
+        for kind in TransitionKind::value_variants() {
+            if *kind != TransitionKind::None {
+                assert!(frame(*kind, 0.0).iter().all(|&c| c == 0), "{:?}", kind);
+            }
+            assert!(frame(*kind, 1.0).iter().all(|&c| c == 200), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_fade_and_wipe_halfway() {
+        assert!(frame(TransitionKind::Fade, 0.5).iter().all(|&c| c == 100));
+
+        let wipe = frame(TransitionKind::Wipe, 0.5);
+        assert_eq!(pixel(&wipe, 1, 0), 200);
+        assert_eq!(pixel(&wipe, 2, 3), 0);
+    }
+
+    #[test]
+    fn test_grow_starts_at_position() {
+        let grow = frame(TransitionKind::Grow, 0.3);
+        assert_eq!(pixel(&grow, 2, 2), 200);
+        assert_eq!(pixel(&grow, 0, 0), 0);
+    }
+
+    #[test]
+    fn test_point_parsing() {
+        assert_eq!("top-left".parse(), Ok(Point { x: 0.0, y: 0.0 }));
+        assert_eq!("0.25, 1".parse(), Ok(Point { x: 0.25, y: 1.0 }));
+        assert!("2,0".parse::<Point>().is_err());
+        assert!("middle".parse::<Point>().is_err());
+    }
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in Easing::value_variants() {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Watches slideshow source directories so the playlist can follow changes on disk.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::errors::{Result, WallrusError};
+
+/// Changes are collected until no event has arrived for this long, so that a burst of events
+/// (or a file still being written by `wallrus download`) results in a single rescan.
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+type Events = notify::Result<Event>;
+
+pub struct SourceWatcher {
+    // Dropping the watcher stops the notifications, so it has to be kept alive.
+    _watcher: RecommendedWatcher,
+    sender: UnboundedSender<Events>,
+    events: UnboundedReceiver<Events>,
+    /// A relevant event arrived but has not been reported yet.
+    pending: bool,
+    sources: Vec<PathBuf>,
+    recursive: bool,
+    /// The source directories that existed when the watches were set up.
+    present: Vec<PathBuf>,
+}
+
+impl SourceWatcher {
+    /// Starts watching every directory in `sources`. Sources that are single files are ignored.
+    /// The parent of each source is watched too, so a source that is removed or unmounted is
+    /// noticed when it comes back.
+    pub fn new(sources: &[PathBuf], recursive: bool) -> Result<Self> {
+        let sources: Vec<PathBuf> = sources
+            .iter()
+            .filter(|source| !source.is_file())
+            .map(|source| std::path::absolute(source).unwrap_or_else(|_| source.clone()))
+            .collect();
+        let (sender, events) = unbounded_channel();
+        let (watcher, present) = watch(&sources, recursive, sender.clone())?;
+        Ok(Self {
+            _watcher: watcher,
+            sender,
+            events,
+            pending: false,
+            sources,
+            recursive,
+            present,
+        })
+    }
+
+    /// Sets the watches up again if a source directory has appeared or disappeared since.
+    pub fn refresh(&mut self) -> Result<()> {
+        if present(&self.sources) != self.present {
+            let (watcher, present) = watch(&self.sources, self.recursive, self.sender.clone())?;
+            self._watcher = watcher;
+            self.present = present;
+        }
+        Ok(())
+    }
+
+    /// Resolves once files have been created, removed or renamed and the burst of events has
+    /// settled. Cancel-safe, so it can be used in `tokio::select!`.
+    pub async fn changed(&mut self) {
+        loop {
+            if self.pending {
+                match tokio::time::timeout(DEBOUNCE, self.events.recv()).await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) | Err(_) => {
+                        self.pending = false;
+                        return;
+                    }
+                }
+            }
+
+            match self.events.recv().await {
+                Some(Ok(event)) if is_relevant(&event) && self.concerns(&event) => {
+                    self.pending = true
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => eprintln!("Warning: file watcher error: {}", e),
+                // The watcher is gone, so there will be no more changes.
+                None => std::future::pending().await,
+            }
+        }
+    }
+}
+
+impl SourceWatcher {
+    /// Whether an event is about a source, rather than a sibling in a watched parent.
+    fn concerns(&self, event: &Event) -> bool {
+        event.paths.is_empty()
+            || event
+                .paths
+                .iter()
+                .any(|path| self.sources.iter().any(|source| path.starts_with(source)))
+    }
+}
+
+/// The source directories that exist.
+fn present(sources: &[PathBuf]) -> Vec<PathBuf> {
+    sources
+        .iter()
+        .filter(|source| source.is_dir())
+        .cloned()
+        .collect()
+}
+
+/// Watches the sources that exist and the parents of all of them.
+fn watch(
+    sources: &[PathBuf],
+    recursive: bool,
+    sender: UnboundedSender<Events>,
+) -> Result<(RecommendedWatcher, Vec<PathBuf>)> {
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    })
+    .map_err(|e| WallrusError::Config(format!("Failed to start file watcher: {}", e)))?;
+
+    let mode = if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    let present = present(sources);
+    for source in &present {
+        watcher
+            .watch(source, mode)
+            .map_err(|e| WallrusError::Config(format!("Failed to watch {:?}: {}", source, e)))?;
+    }
+    let parents: HashSet<&Path> = sources
+        .iter()
+        .filter_map(|source| source.parent())
+        .collect();
+    for parent in parents.into_iter().filter(|parent| parent.is_dir()) {
+        // Only a convenience: without it a returning source is found on the next reload.
+        let _ = watcher.watch(parent, RecursiveMode::NonRecursive);
+    }
+    Ok((watcher, present))
+}
+
+fn is_relevant(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_) | EventKind::Any
+    )
+}
@@ -0,0 +1,270 @@
+//! SHM storage for a wallpaper surface.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use smithay_client_toolkit::shm::{raw::RawPool, Shm};
+use wayland_client::{
+    protocol::{wl_buffer, wl_shm, wl_surface::WlSurface},
+    Connection, Dispatch, QueueHandle,
+};
+
+use super::state::WallpaperState;
+use crate::errors::{Result, WallrusError};
+
+/// Two buffers are enough while the compositor releases them promptly; a third covers
+/// compositors that hold on to the previous frame. Beyond that, drawing waits.
+const MAX_BUFFERS: usize = 3;
+
+/// Argb8888 buffers of one size, sharing a single SHM pool. A buffer is only drawn into
+/// once the compositor has released it, and buffers are reused from frame to frame. The
+/// pool is freed when it is dropped, which happens when the surface is resized or its
+/// output goes away.
+pub struct BufferPool {
+    pool: RawPool,
+    slots: Vec<Slot>,
+    width: u32,
+    height: u32,
+    qh: QueueHandle<WallpaperState>,
+    /// The buffer on screen.
+    front: Option<usize>,
+    /// The buffer being drawn into.
+    back: Option<usize>,
+}
+
+struct Slot {
+    buffer: wl_buffer::WlBuffer,
+    /// Set on attach and cleared when the compositor sends `release`.
+    busy: Arc<AtomicBool>,
+}
+
+/// A rectangle of buffer pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl BufferPool {
+    pub fn new(
+        shm: &Shm,
+        width: u32,
+        height: u32,
+        qh: &QueueHandle<WallpaperState>,
+    ) -> Result<Self> {
+        let len = width as usize * height as usize * 4;
+        let pool = RawPool::new(len * 2, shm)
+            .map_err(|e| WallrusError::Config(format!("Failed to create SHM pool: {}", e)))?;
+        let mut buffers = Self {
+            pool,
+            slots: Vec::new(),
+            width,
+            height,
+            qh: qh.clone(),
+            front: None,
+            back: None,
+        };
+        buffers.add_slot()?;
+        buffers.add_slot()?;
+        Ok(buffers)
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn frame_len(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+
+    /// Adds a buffer at the end of the pool, growing it if needed.
+    fn add_slot(&mut self) -> Result<()> {
+        let len = self.frame_len();
+        let offset = self.slots.len() * len;
+        if self.pool.len() < offset + len {
+            self.pool.resize(offset + len)?;
+        }
+        let busy = Arc::new(AtomicBool::new(false));
+        let buffer = self.pool.create_buffer(
+            offset as i32,
+            self.width as i32,
+            self.height as i32,
+            self.width as i32 * 4,
+            wl_shm::Format::Argb8888,
+            busy.clone(),
+            &self.qh,
+        );
+        self.slots.push(Slot { buffer, busy });
+        Ok(())
+    }
+
+    /// The frame currently on screen, if any.
+    pub fn front(&mut self) -> Option<&[u8]> {
+        let len = self.frame_len();
+        let start = self.front? * len;
+        Some(&self.pool.mmap()[start..start + len])
+    }
+
+    /// A released buffer to draw the next frame into, as little-endian BGRA. Returns `None`
+    /// while the compositor still holds every buffer.
+    pub fn back_mut(&mut self) -> Result<Option<&mut [u8]>> {
+        let free = (0..self.slots.len()).find(|&index| {
+            Some(index) != self.front && !self.slots[index].busy.load(Ordering::Acquire)
+        });
+        let index = match free {
+            Some(index) => index,
+            None if self.slots.len() < MAX_BUFFERS => {
+                self.add_slot()?;
+                self.slots.len() - 1
+            }
+            None => return Ok(None),
+        };
+        self.back = Some(index);
+
+        let len = self.frame_len();
+        let start = index * len;
+        Ok(Some(&mut self.pool.mmap()[start..start + len]))
+    }
+
+    /// Attaches the buffer last returned by `back_mut` to `surface` and damages the part
+    /// that differs from the frame on screen. The caller commits the surface.
+    pub fn attach(&mut self, surface: &WlSurface) {
+        let Some(back) = self.back.take() else {
+            return;
+        };
+        let len = self.frame_len();
+        let (width, height) = (self.width, self.height);
+        let mmap = self.pool.mmap();
+        let damage = match self.front {
+            Some(front) => changed_region(
+                &mmap[front * len..(front + 1) * len],
+                &mmap[back * len..(back + 1) * len],
+                width,
+                height,
+            ),
+            None => Some(Region {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }),
+        };
+
+        let slot = &self.slots[back];
+        slot.busy.store(true, Ordering::Release);
+        surface.attach(Some(&slot.buffer), 0, 0);
+        if let Some(region) = damage {
+            surface.damage_buffer(
+                region.x as i32,
+                region.y as i32,
+                region.width as i32,
+                region.height as i32,
+            );
+        }
+        self.front = Some(back);
+    }
+}
+
+impl Drop for BufferPool {
+    fn drop(&mut self) {
+        for slot in &self.slots {
+            slot.buffer.destroy();
+        }
+    }
+}
+
+/// Converts RGBA pixels to the little-endian BGRA layout of Argb8888, in parallel and in a
+/// form the compiler vectorizes.
+pub fn write_bgra(rgba: &[u8], bgra: &mut [u8]) {
+    // Enough rows of a 4K frame per task to keep the threads busy without much overhead.
+    const CHUNK: usize = 64 * 1024;
+    bgra.par_chunks_mut(CHUNK)
+        .zip(rgba.par_chunks(CHUNK))
+        .for_each(|(bgra, rgba)| {
+            for (out, pixel) in bgra.chunks_exact_mut(4).zip(rgba.chunks_exact(4)) {
+                let pixel = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                let swapped =
+                    (pixel & 0xff00_ff00) | ((pixel & 0x0000_00ff) << 16) | ((pixel >> 16) & 0xff);
+                out.copy_from_slice(&swapped.to_le_bytes());
+            }
+        });
+}
+
+/// The smallest rectangle containing every pixel that differs between two frames of
+/// `width` x `height` pixels, or `None` if they are identical.
+pub fn changed_region(old: &[u8], new: &[u8], width: u32, height: u32) -> Option<Region> {
+    let stride = width as usize * 4;
+    let rows = old.chunks_exact(stride).zip(new.chunks_exact(stride));
+
+    let mut region: Option<(usize, usize, usize, usize)> = None;
+    for (y, (old_row, new_row)) in rows.enumerate().take(height as usize) {
+        if old_row == new_row {
+            continue;
+        }
+        let pixels = || old_row.chunks_exact(4).zip(new_row.chunks_exact(4));
+        let first = pixels().position(|(a, b)| a != b).unwrap_or(0);
+        let last = pixels().rposition(|(a, b)| a != b).unwrap_or(first);
+        region = Some(match region {
+            None => (first, y, last, y),
+            Some((left, top, right, _)) => (left.min(first), top, right.max(last), y),
+        });
+    }
+
+    region.map(|(left, top, right, bottom)| Region {
+        x: left as u32,
+        y: top as u32,
+        width: (right - left + 1) as u32,
+        height: (bottom - top + 1) as u32,
+    })
+}
+
+impl Dispatch<wl_buffer::WlBuffer, Arc<AtomicBool>> for WallpaperState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_buffer::WlBuffer,
+        event: wl_buffer::Event,
+        busy: &Arc<AtomicBool>,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            busy.store(false, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_bgra_swaps_red_and_blue() {
+        let rgba = [1, 2, 3, 4, 10, 20, 30, 40];
+        let mut bgra = [0; 8];
+        write_bgra(&rgba, &mut bgra);
+        assert_eq!(bgra, [3, 2, 1, 4, 30, 20, 10, 40]);
+    }
+
+    #[test]
+    fn test_changed_region() {
+        let old = vec![0u8; 4 * 3 * 4];
+        assert_eq!(changed_region(&old, &old, 4, 3), None);
+
+        let mut new = old.clone();
+        // Pixels (1, 0) and (2, 1) change.
+        new[4] = 1;
+        new[(4 + 2) * 4 + 3] = 1;
+        assert_eq!(
+            changed_region(&old, &new, 4, 3),
+            Some(Region {
+                x: 1,
+                y: 0,
+                width: 2,
+                height: 2
+            })
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Hands wallpapers to the daemon, starting it on first use.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::wl_registry,
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::ZwlrLayerShellV1;
+
+use super::ipc::{self, Request, Response};
+use crate::engine::{OutputStatus, Paint, Placement, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::state_dir;
+
+/// How long to wait for a freshly started daemon to accept connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The daemon's output goes here when it is started automatically.
+const LOG_FILE: &str = "daemon.log";
+
+/// Whether the compositor advertises `zwlr_layer_shell_v1`, which the wallpaper daemon needs.
+/// The registry is only probed once per process.
+pub fn has_layer_shell() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| match Connection::connect_to_env() {
+        Ok(conn) => advertises_layer_shell(&conn),
+        Err(_) => false,
+    })
+}
+
+fn advertises_layer_shell(conn: &Connection) -> bool {
+    let Ok((globals, _)) = registry_queue_init::<Probe>(conn) else {
+        return false;
+    };
+    let interface = ZwlrLayerShellV1::interface().name;
+    globals
+        .contents()
+        .with_list(|list| list.iter().any(|global| global.interface == interface))
+}
+
+/// Only reads the initial list of globals.
+struct Probe;
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Probe {
+    fn event(
+        _state: &mut Self,
+        _proxy: &wl_registry::WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+/// Sets the wallpaper through the wallpaper daemon, on one output (matched by name or
+/// description) or on all of them. Returns as soon as the daemon has taken over the image;
+/// the transition runs in the daemon.
+pub fn set_wayland_wallpaper(
+    image_path: &Path,
+    output: Option<&str>,
+    placement: &Placement,
+    transition: &Transition,
+) -> Result<()> {
+    request(&Request::Set {
+        image: image_path.canonicalize()?,
+        output: output.map(str::to_string),
+        placement: *placement,
+        transition: *transition,
+    })?
+    .into_result()
+}
+
+/// Sets the wallpaper of a single output, matched by name (`DP-1`) or description.
+#[cfg(not(target_os = "linux"))]
+pub fn set_output_wallpaper(
+    image_path: &Path,
+    output: &str,
+    placement: &Placement,
+    transition: &Transition,
+) -> Result<()> {
+    set_wayland_wallpaper(image_path, Some(output), placement, transition)
+}
+
+/// Sets a solid color or gradient through the wallpaper daemon, on one output or on all of
+/// them. The daemon draws it straight into the surfaces' buffers.
+pub fn set_wayland_paint(
+    paint: &Paint,
+    output: Option<&str>,
+    transition: &Transition,
+) -> Result<()> {
+    request(&Request::Paint {
+        paint: *paint,
+        output: output.map(str::to_string),
+        transition: *transition,
+    })?
+    .into_result()
+}
+
+/// Sets a solid color or gradient on a single output, matched by name or description.
+pub fn set_output_paint(paint: &Paint, output: &str, transition: &Transition) -> Result<()> {
+    set_wayland_paint(paint, Some(output), transition)
+}
+
+/// Lists the outputs known to the daemon.
+pub fn list_outputs() -> Result<Vec<OutputStatus>> {
+    let response = request(&Request::Outputs)?;
+    let outputs = response.outputs.clone().unwrap_or_default();
+    response.into_result()?;
+    Ok(outputs)
+}
+
+/// The image a running daemon shows on its first output. Does not start the daemon.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn current_wallpaper() -> Option<PathBuf> {
+    let response = ipc::send(ipc::connect().ok()?, &Request::Outputs).ok()?;
+    response
+        .outputs?
+        .into_iter()
+        .find_map(|output| output.image)
+}
+
+/// Sends a request to the daemon, starting it if it is not running.
+fn request(request: &Request) -> Result<Response> {
+    let stream = match ipc::connect() {
+        Ok(stream) => stream,
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            start_daemon()?
+        }
+        Err(e) => return Err(e.into()),
+    };
+    ipc::send(stream, request)
+}
+
+/// Asks a running daemon to remove its surfaces and exit.
+pub fn stop_daemon() -> Result<()> {
+    let stream = ipc::connect().map_err(|e| match e.kind() {
+        ErrorKind::NotFound | ErrorKind::ConnectionRefused => {
+            WallrusError::Config("No wallpaper daemon is running".into())
+        }
+        _ => e.into(),
+    })?;
+    ipc::send(stream, &Request::Stop)?.into_result()
+}
+
+/// Starts `wallrus daemon` in the background and waits until it accepts connections.
+fn start_daemon() -> Result<UnixStream> {
+    let log_path = state_dir()?.join(LOG_FILE);
+    if let Some(dir) = log_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let log = File::create(&log_path)?;
+
+    let mut child = Command::new(env::current_exe()?)
+        .arg("daemon")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        // Keep the daemon alive when the terminal that started it sends Ctrl+C.
+        .process_group(0)
+        .spawn()?;
+
+    let start = Instant::now();
+    loop {
+        if let Ok(stream) = ipc::connect() {
+            return Ok(stream);
+        }
+        if let Some(status) = child.try_wait()? {
+            return Err(WallrusError::Config(format!(
+                "Wallpaper daemon exited ({}), see {:?}",
+                status, log_path
+            )));
+        }
+        if start.elapsed() > STARTUP_TIMEOUT {
+            return Err(WallrusError::Config(format!(
+                "Wallpaper daemon did not start within {:?}, see {:?}",
+                STARTUP_TIMEOUT, log_path
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_compositor::TestCompositor;
+    use super::*;
+
+    #[test]
+    fn test_probes_layer_shell() {
+        let compositor = TestCompositor::new(&[("DP-1", 16, 16)]);
+        assert!(advertises_layer_shell(&compositor.connect()));
+
+        compositor.remove_layer_shell();
+        assert!(!advertises_layer_shell(&compositor.connect()));
+    }
+}
@@ -0,0 +1,208 @@
+//! `wallrus daemon`: owns the layer surfaces and swaps their images on request.
+
+use std::fs;
+use std::io::BufReader;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use smithay_client_toolkit::reexports::{
+    calloop::{
+        channel::{self, Channel, Sender},
+        EventLoop,
+    },
+    calloop_wayland_source::WaylandSource,
+};
+use wayland_client::{globals::registry_queue_init, Connection};
+
+use super::ipc::{self, Request, Response};
+use super::state::{Wallpaper, WallpaperState};
+use crate::engine::{Placement, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::{claim_socket, open_image};
+
+/// A request for the Wayland thread, with the channel to answer on.
+struct Command {
+    action: Action,
+    reply: mpsc::Sender<Response>,
+}
+
+enum Action {
+    Show {
+        wallpaper: Wallpaper,
+        output: Option<String>,
+        transition: Transition,
+    },
+    Outputs,
+    Stop,
+}
+
+/// Connects to the compositor and serves wallpaper requests until stopped or disconnected.
+pub fn run_daemon() -> Result<()> {
+    let conn = Connection::connect_to_env()
+        .map_err(|e| WallrusError::Config(format!("Failed to connect to Wayland: {}", e)))?;
+    let (globals, mut event_queue) = registry_queue_init(&conn)
+        .map_err(|e| WallrusError::Config(format!("Failed to initialize registry: {}", e)))?;
+    let qh = event_queue.handle();
+
+    let mut state = WallpaperState::new(&globals, &qh)?;
+    // Discover the outputs before accepting requests.
+    event_queue
+        .roundtrip(&mut state)
+        .map_err(|e| WallrusError::Config(format!("Failed initial roundtrip: {}", e)))?;
+
+    let mut event_loop: EventLoop<WallpaperState> = EventLoop::try_new()
+        .map_err(|e| WallrusError::Config(format!("Failed to create event loop: {}", e)))?;
+    WaylandSource::new(conn, event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| WallrusError::Config(format!("Failed to watch Wayland socket: {}", e)))?;
+
+    let (sender, commands): (Sender<Command>, Channel<Command>) = channel::channel();
+    event_loop
+        .handle()
+        .insert_source(commands, |event, _, state| {
+            if let channel::Event::Msg(command) = event {
+                let response = match command.action {
+                    Action::Show {
+                        wallpaper,
+                        output,
+                        transition,
+                    } => {
+                        Response::new(state.set_wallpaper(wallpaper, output.as_deref(), transition))
+                    }
+                    Action::Outputs => Response::with_outputs(state.outputs()),
+                    Action::Stop => {
+                        state.exit = true;
+                        Response::new(Ok(()))
+                    }
+                };
+                let _ = command.reply.send(response);
+            }
+        })
+        .map_err(|e| WallrusError::Config(format!("Failed to watch requests: {}", e)))?;
+
+    let path = ipc::socket_path()?;
+    if !claim_socket(&path)? {
+        return Err(WallrusError::Config(format!(
+            "Another wallpaper daemon is already running (socket {:?})",
+            path
+        )));
+    }
+    let listener = UnixListener::bind(&path).map_err(|e| {
+        WallrusError::Config(format!("Failed to bind daemon socket {:?}: {}", path, e))
+    })?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    thread::spawn(move || accept(listener, sender));
+    println!("Wallpaper daemon listening on {:?}", path);
+
+    let result = loop {
+        if state.exit {
+            break Ok(());
+        }
+        if let Err(e) = event_loop.dispatch(None, &mut state) {
+            break Err(WallrusError::Config(format!(
+                "Wayland event loop failed: {}",
+                e
+            )));
+        }
+    };
+
+    let _ = fs::remove_file(&path);
+    result
+}
+
+fn accept(listener: UnixListener, commands: Sender<Command>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let commands = commands.clone();
+                thread::spawn(move || serve(stream, commands));
+            }
+            Err(e) => eprintln!("Warning: daemon socket error: {}", e),
+        }
+    }
+}
+
+/// Answers one request. Images are decoded here so the Wayland thread stays responsive.
+fn serve(mut stream: UnixStream, commands: Sender<Command>) {
+    let request = match stream
+        .try_clone()
+        .map_err(WallrusError::from)
+        .and_then(|read| ipc::read_message::<Request>(&mut BufReader::new(read)))
+    {
+        Ok(Some(request)) => request,
+        Ok(None) => return,
+        Err(e) => {
+            let _ = ipc::write_message(&mut stream, &Response::new(Err(e)));
+            return;
+        }
+    };
+
+    match request {
+        Request::Set {
+            image,
+            output,
+            placement,
+            transition,
+        } => {
+            let response = match load(image, placement) {
+                Ok(wallpaper) => run(
+                    &commands,
+                    Action::Show {
+                        wallpaper,
+                        output,
+                        transition,
+                    },
+                ),
+                Err(e) => Response::new(Err(e)),
+            };
+            let _ = ipc::write_message(&mut stream, &response);
+        }
+        Request::Paint {
+            paint,
+            output,
+            transition,
+        } => {
+            println!("Setting wallpaper: {}", paint);
+            let action = Action::Show {
+                wallpaper: Wallpaper::Paint(paint),
+                output,
+                transition,
+            };
+            let _ = ipc::write_message(&mut stream, &run(&commands, action));
+        }
+        Request::Outputs => {
+            let _ = ipc::write_message(&mut stream, &run(&commands, Action::Outputs));
+        }
+        Request::Stop => {
+            // Answer first: the process exits as soon as the Wayland thread stops.
+            let _ = ipc::write_message(&mut stream, &Response::new(Ok(())));
+            let _ = run(&commands, Action::Stop);
+        }
+    }
+}
+
+fn load(path: PathBuf, placement: Placement) -> Result<Wallpaper> {
+    println!("Setting wallpaper: {:?}", path);
+    // Decoded once, then shared by every output.
+    let image = open_image(&path)?.into_rgba8();
+    Ok(Wallpaper::Image {
+        path,
+        image,
+        placement,
+    })
+}
+
+/// Hands an action to the Wayland thread and waits for it to be carried out.
+fn run(commands: &Sender<Command>, action: Action) -> Response {
+    let (reply, response) = mpsc::channel();
+    // If the Wayland thread has stopped, the command and its reply channel are dropped.
+    let _ = commands.send(Command { action, reply });
+    response.recv().unwrap_or_else(|_| {
+        Response::new(Err(WallrusError::Config(
+            "Wallpaper daemon is shutting down".into(),
+        )))
+    })
+}
@@ -0,0 +1,151 @@
+//! The socket protocol between `wallrus daemon` and the commands that set wallpapers.
+//!
+//! Like the slideshow control socket, it speaks newline-delimited JSON with one request and
+//! one response per connection.
+
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{OutputStatus, Paint, Placement, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::utils::runtime_dir;
+
+/// How long a client waits for the daemon to answer. Decoding a large image takes a while.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Show an image on one output (by name or description), or on every output
+    Set {
+        image: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<String>,
+        #[serde(default)]
+        placement: Placement,
+        #[serde(default)]
+        transition: Transition,
+    },
+    /// Show a solid color or gradient on one output, or on every output
+    Paint {
+        paint: Paint,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<String>,
+        #[serde(default)]
+        transition: Transition,
+    },
+    /// List the outputs and what they show
+    Outputs,
+    /// Remove the wallpaper surfaces and exit
+    Stop,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Vec<OutputStatus>>,
+}
+
+impl Response {
+    pub fn new(result: Result<()>) -> Self {
+        Self {
+            ok: result.is_ok(),
+            error: result.err().map(|e| match e {
+                WallrusError::Config(message) => message,
+                e => e.to_string(),
+            }),
+            outputs: None,
+        }
+    }
+
+    pub fn with_outputs(outputs: Vec<OutputStatus>) -> Self {
+        Self {
+            outputs: Some(outputs),
+            ..Self::new(Ok(()))
+        }
+    }
+
+    pub fn into_result(self) -> Result<()> {
+        match (self.ok, self.error) {
+            (true, _) => Ok(()),
+            (false, error) => {
+                Err(WallrusError::Config(error.unwrap_or_else(|| {
+                    "Wallpaper daemon reported an error".into()
+                })))
+            }
+        }
+    }
+}
+
+/// Location of the daemon socket. Each Wayland display gets its own daemon.
+pub fn socket_path() -> Result<PathBuf> {
+    let display = env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".into());
+    // WAYLAND_DISPLAY may also be an absolute socket path.
+    let display = Path::new(&display)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(display);
+    Ok(runtime_dir()?.join(format!("wallrus-{}.sock", display)))
+}
+
+/// Connects to the running daemon.
+pub fn connect() -> std::io::Result<UnixStream> {
+    UnixStream::connect(socket_path().map_err(std::io::Error::other)?)
+}
+
+/// Sends a request on a fresh connection and waits for the answer.
+pub fn send(mut stream: UnixStream, request: &Request) -> Result<Response> {
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    write_message(&mut stream, request)?;
+    read_message(&mut BufReader::new(stream))?
+        .ok_or_else(|| WallrusError::Config("Wallpaper daemon closed the connection".into()))
+}
+
+pub fn write_message<T: Serialize>(stream: &mut UnixStream, message: &T) -> Result<()> {
+    let mut data = serde_json::to_vec(message)
+        .map_err(|e| WallrusError::Config(format!("Failed to encode message: {}", e)))?;
+    data.push(b'\n');
+    stream.write_all(&data)?;
+    Ok(())
+}
+
+/// Reads one message. Returns `None` if the peer hung up without sending anything.
+pub fn read_message<T: for<'de> Deserialize<'de>>(reader: &mut impl BufRead) -> Result<Option<T>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| WallrusError::Config(format!("Invalid message: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_round_trip() {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        let request = Request::Set {
+            image: PathBuf::from("/tmp/a.png"),
+            output: Some("DP-1".into()),
+            placement: Placement::default(),
+            transition: Transition::default(),
+        };
+        write_message(&mut client, &request).unwrap();
+        drop(client);
+
+        let mut reader = BufReader::new(server);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(request));
+        assert_eq!(read_message::<Request>(&mut reader).unwrap(), None);
+    }
+}
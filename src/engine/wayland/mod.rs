@@ -0,0 +1,22 @@
+//! Native Wayland wallpaper implementation using the wlr-layer-shell protocol.
+//!
+//! Layer surfaces only exist while the client that created them is connected, so the
+//! surfaces are owned by a long-running `wallrus daemon`. Setting a wallpaper sends the image
+//! to the daemon over a Unix socket (starting the daemon if needed) and returns immediately;
+//! the daemon attaches a fresh buffer to its existing surfaces.
+
+mod buffer;
+mod client;
+mod daemon;
+mod ipc;
+mod state;
+#[cfg(test)]
+mod test_compositor;
+
+#[cfg(not(target_os = "linux"))]
+pub use client::set_output_wallpaper;
+pub use client::{
+    current_wallpaper, has_layer_shell, list_outputs, set_output_paint, set_wayland_paint,
+    set_wayland_wallpaper, stop_daemon,
+};
+pub use daemon::run_daemon;
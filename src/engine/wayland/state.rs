@@ -0,0 +1,946 @@
+//! Layer surfaces and the Wayland event handlers of the wallpaper daemon.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_shm,
+    delegate_simple,
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState, SimpleGlobal},
+    registry_handlers,
+    shell::wlr_layer::{
+        Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+        LayerSurfaceConfigure,
+    },
+    shm::{Shm, ShmHandler},
+};
+use wayland_client::{
+    globals::GlobalList,
+    protocol::{wl_buffer, wl_output, wl_surface},
+    Connection, Dispatch, QueueHandle,
+};
+use wayland_protocols::wp::{
+    fractional_scale::v1::client::{
+        wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+        wp_fractional_scale_v1::{self, WpFractionalScaleV1},
+    },
+    single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1,
+    viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
+};
+
+use super::buffer::{write_bgra, BufferPool};
+use crate::engine::color::Rgb;
+use crate::engine::transition::{self, Transition};
+use crate::engine::{scaling, OutputStatus, Paint, Placement};
+use crate::errors::{Result, WallrusError};
+
+/// Fractional scales are sent in 120ths.
+const SCALE_DENOMINATOR: u32 = 120;
+
+/// What the daemon shows: a decoded image, the file it came from and how it is fitted to
+/// each output, or a color or gradient drawn at each output's size.
+pub enum Wallpaper {
+    Image {
+        path: PathBuf,
+        image: RgbaImage,
+        placement: Placement,
+    },
+    Paint(Paint),
+}
+
+impl Wallpaper {
+    fn render(&self, width: u32, height: u32) -> RgbaImage {
+        match self {
+            Self::Image {
+                image, placement, ..
+            } => scaling::render(image, width, height, placement),
+            Self::Paint(paint) => paint.render(width, height),
+        }
+    }
+}
+
+/// A surface's next frame: pixels for its SHM buffers, or a solid color for a single-pixel
+/// buffer.
+enum Frame {
+    Pixels(RgbaImage),
+    Solid(Rgb),
+}
+
+pub struct WallpaperState {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    compositor_state: CompositorState,
+    shm_state: Shm,
+    layer_shell: LayerShell,
+    /// Maps buffers to the logical surface size. Together with fractional scaling it lets
+    /// buffers match the physical resolution at any scale; without it only integer scales do.
+    viewporter: Option<SimpleGlobal<WpViewporter, 1>>,
+    fractional_scale_manager: Option<SimpleGlobal<WpFractionalScaleManagerV1, 1>>,
+    /// Makes 1x1 buffers of any color, which the viewport stretches over the surface. Solid
+    /// colors are drawn into SHM buffers without it.
+    single_pixel_buffer_manager: Option<SimpleGlobal<WpSinglePixelBufferManagerV1, 1>>,
+    qh: QueueHandle<Self>,
+
+    /// The image shown on outputs without an assignment. Images are kept to repaint
+    /// outputs that appear or change size later.
+    default: Option<Arc<Wallpaper>>,
+    /// Images assigned to single outputs, by output name. Kept while an output is unplugged,
+    /// so it shows the same image when it comes back.
+    assigned: HashMap<String, Arc<Wallpaper>>,
+    surfaces: Vec<WallpaperSurface>,
+    pub exit: bool,
+}
+
+struct WallpaperSurface {
+    surface: wl_surface::WlSurface,
+    layer_surface: LayerSurface,
+    output: wl_output::WlOutput,
+    name: Option<String>,
+    description: Option<String>,
+    /// Logical size from the last configure.
+    width: u32,
+    height: u32,
+    /// Output scale in 120ths, from `wp_fractional_scale_v1` if the compositor supports it
+    /// and the integer buffer scale otherwise.
+    scale: u32,
+    viewport: Option<WpViewport>,
+    fractional_scale: Option<WpFractionalScaleV1>,
+    configured: bool,
+    /// Recreated whenever the buffer size changes.
+    buffers: Option<BufferPool>,
+    /// The single-pixel buffer on screen and its color, when showing a solid color. The SHM
+    /// buffers are freed meanwhile.
+    single_pixel: Option<(wl_buffer::WlBuffer, Rgb)>,
+    animation: Option<Animation>,
+    /// The image last drawn and the buffer size it was drawn at, to skip configures that
+    /// change nothing.
+    shown: Option<(Arc<Wallpaper>, (u32, u32))>,
+}
+
+/// A frame waiting to be shown, through a transition in progress or as soon as a buffer is
+/// free. Advanced on every frame callback.
+struct Animation {
+    from: Vec<u8>,
+    to: Vec<u8>,
+    transition: Transition,
+    start: Instant,
+}
+
+impl WallpaperState {
+    pub fn new(globals: &GlobalList, qh: &QueueHandle<Self>) -> Result<Self> {
+        Ok(Self {
+            registry_state: RegistryState::new(globals),
+            output_state: OutputState::new(globals, qh),
+            compositor_state: CompositorState::bind(globals, qh)
+                .map_err(|e| WallrusError::Config(format!("Failed to bind compositor: {}", e)))?,
+            shm_state: Shm::bind(globals, qh)
+                .map_err(|e| WallrusError::Config(format!("Failed to bind shm: {}", e)))?,
+            layer_shell: LayerShell::bind(globals, qh).map_err(|e| {
+                WallrusError::Config(format!(
+                    "Failed to bind layer shell, the compositor may not support \
+                     wlr-layer-shell: {}",
+                    e
+                ))
+            })?,
+            // Both are optional: without them buffers are drawn at integer scales.
+            viewporter: SimpleGlobal::bind(globals, qh).ok(),
+            fractional_scale_manager: SimpleGlobal::bind(globals, qh).ok(),
+            single_pixel_buffer_manager: SimpleGlobal::bind(globals, qh).ok(),
+            qh: qh.clone(),
+            default: None,
+            assigned: HashMap::new(),
+            surfaces: Vec::new(),
+            exit: false,
+        })
+    }
+
+    /// Shows a wallpaper on the output matching `output` by name or description, or on every
+    /// output, animating the change with `transition`. Surfaces that are configured later
+    /// pick it up when they are.
+    pub fn set_wallpaper(
+        &mut self,
+        wallpaper: Wallpaper,
+        output: Option<&str>,
+        transition: Transition,
+    ) -> Result<()> {
+        let wallpaper = Arc::new(wallpaper);
+        let Some(output) = output else {
+            self.default = Some(wallpaper);
+            self.assigned.clear();
+            let all: Vec<usize> = (0..self.surfaces.len()).collect();
+            return self.draw_all(&all, Some(transition));
+        };
+
+        let matching: Vec<usize> = (0..self.surfaces.len())
+            .filter(|&index| self.surfaces[index].matches(output))
+            .collect();
+        if matching.is_empty() {
+            let names: Vec<&str> = self
+                .surfaces
+                .iter()
+                .filter_map(|surface| surface.name.as_deref())
+                .collect();
+            return Err(WallrusError::Config(format!(
+                "No output named {:?}, available outputs: {}",
+                output,
+                names.join(", ")
+            )));
+        }
+
+        for &index in &matching {
+            let key = self.surfaces[index].key().unwrap_or(output).to_string();
+            self.assigned.insert(key, wallpaper.clone());
+        }
+        self.draw_all(&matching, Some(transition))
+    }
+
+    /// The outputs with a wallpaper surface and what they show.
+    pub fn outputs(&self) -> Vec<OutputStatus> {
+        self.surfaces
+            .iter()
+            .map(|surface| {
+                let mode = self.output_state.info(&surface.output).and_then(|info| {
+                    let (width, height) = info.modes.iter().find(|mode| mode.current)?.dimensions;
+                    // Modes are in the panel's own orientation; rotated outputs are used
+                    // the other way round.
+                    Some(match info.transform {
+                        wl_output::Transform::_90
+                        | wl_output::Transform::_270
+                        | wl_output::Transform::Flipped90
+                        | wl_output::Transform::Flipped270 => (height, width),
+                        _ => (width, height),
+                    })
+                });
+                let (width, height) = match mode {
+                    Some((width, height)) => (width as u32, height as u32),
+                    None => (surface.width, surface.height),
+                };
+                OutputStatus {
+                    name: surface.name.clone(),
+                    description: surface.description.clone(),
+                    width,
+                    height,
+                    image: match self.wallpaper_for(surface).map(|w| &**w) {
+                        Some(Wallpaper::Image { path, .. }) => Some(path.clone()),
+                        _ => None,
+                    },
+                    paint: match self.wallpaper_for(surface).map(|w| &**w) {
+                        Some(Wallpaper::Paint(paint)) => Some(*paint),
+                        _ => None,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    fn wallpaper_for(&self, surface: &WallpaperSurface) -> Option<&Arc<Wallpaper>> {
+        surface
+            .key()
+            .and_then(|key| self.assigned.get(key))
+            .or(self.default.as_ref())
+    }
+
+    /// Fits the current image to a surface at its physical resolution and shows it. See
+    /// `draw_all`.
+    fn draw(&mut self, index: usize, transition: Option<Transition>) -> Result<()> {
+        self.draw_all(&[index], transition)
+    }
+
+    /// Fits the current image to each of the surfaces at its physical resolution and shows
+    /// it, through `transition` if one is given and the surface already shows something at
+    /// that size. The surfaces are rendered in parallel. Without a transition, surfaces that
+    /// already show their image at this size are left alone.
+    fn draw_all(&mut self, indices: &[usize], transition: Option<Transition>) -> Result<()> {
+        let jobs: Vec<(usize, Arc<Wallpaper>, (u32, u32))> = indices
+            .iter()
+            .filter_map(|&index| {
+                let (wallpaper, size) = self.target(index)?;
+                let up_to_date =
+                    self.surfaces[index]
+                        .shown
+                        .as_ref()
+                        .is_some_and(|(shown, shown_size)| {
+                            Arc::ptr_eq(shown, &wallpaper) && *shown_size == size
+                        });
+                (transition.is_some() || !up_to_date).then_some((index, wallpaper, size))
+            })
+            .collect();
+
+        // Solid colors skip rendering where a single-pixel buffer can show them, unless a
+        // transition has to blend them.
+        let animated = transition.is_some_and(|transition| transition.is_animated());
+        let frames: Vec<Frame> = jobs
+            .par_iter()
+            .map(|(index, wallpaper, (width, height))| match **wallpaper {
+                Wallpaper::Paint(Paint::Color(color))
+                    if !animated && self.can_show_single_pixel(*index) =>
+                {
+                    Frame::Solid(color)
+                }
+                _ => Frame::Pixels(wallpaper.render(*width, *height)),
+            })
+            .collect();
+
+        for ((index, wallpaper, size), frame) in jobs.into_iter().zip(frames) {
+            self.surfaces[index].shown = Some((wallpaper, size));
+            match frame {
+                Frame::Pixels(frame) => self.show(index, frame, transition)?,
+                Frame::Solid(color) => self.show_single_pixel(index, color)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn can_show_single_pixel(&self, index: usize) -> bool {
+        self.single_pixel_buffer_manager.is_some() && self.surfaces[index].viewport.is_some()
+    }
+
+    /// Shows a solid color as a single-pixel buffer stretched over the surface, and frees the
+    /// surface's SHM buffers.
+    fn show_single_pixel(&mut self, index: usize, color: Rgb) -> Result<()> {
+        let surface = &mut self.surfaces[index];
+        let (Some(manager), Some(viewport)) =
+            (&self.single_pixel_buffer_manager, &surface.viewport)
+        else {
+            return Ok(());
+        };
+        let manager = manager.get().map_err(|e| {
+            WallrusError::Config(format!("Failed to bind single-pixel buffers: {}", e))
+        })?;
+
+        // Channels are scaled from 8 to 32 bits.
+        let channel = |value: u8| value as u32 * 0x0101_0101;
+        let buffer = manager.create_u32_rgba_buffer(
+            channel(color.0),
+            channel(color.1),
+            channel(color.2),
+            u32::MAX,
+            &self.qh,
+            Arc::new(AtomicBool::new(false)),
+        );
+        surface.animation = None;
+        viewport.set_destination(surface.width as i32, surface.height as i32);
+        surface.surface.attach(Some(&buffer), 0, 0);
+        surface.surface.damage_buffer(0, 0, 1, 1);
+        surface.surface.commit();
+
+        if let Some((replaced, _)) = surface.single_pixel.replace((buffer, color)) {
+            replaced.destroy();
+        }
+        surface.buffers = None;
+        Ok(())
+    }
+
+    /// The image a surface should show and the buffer size to draw it at, if it has been
+    /// configured.
+    fn target(&self, index: usize) -> Option<(Arc<Wallpaper>, (u32, u32))> {
+        let surface = &self.surfaces[index];
+        if !surface.configured || surface.width == 0 || surface.height == 0 {
+            return None;
+        }
+        let wallpaper = self.wallpaper_for(surface)?.clone();
+        Some((wallpaper, surface.buffer_size()))
+    }
+
+    /// Shows a rendered frame on a surface, starting `transition` or writing it straight into
+    /// a free buffer.
+    fn show(
+        &mut self,
+        index: usize,
+        frame: RgbaImage,
+        transition: Option<Transition>,
+    ) -> Result<()> {
+        let surface = &mut self.surfaces[index];
+        let size = frame.dimensions();
+
+        // A new size needs new buffers. The old pool stays alive until the new frame has
+        // been committed, and is freed with it.
+        let mut replaced = None;
+        if surface.buffers.as_ref().map(BufferPool::size) != Some(size) {
+            let buffers = BufferPool::new(&self.shm_state, size.0, size.1, &self.qh)?;
+            replaced = surface.buffers.replace(buffers);
+        }
+        let Some(buffers) = surface.buffers.as_mut() else {
+            return Ok(());
+        };
+
+        // A transition starts from whatever is on screen, even halfway through another one.
+        let single_pixel = surface.single_pixel.as_ref().map(|(_, color)| *color);
+        let from = transition
+            .filter(Transition::is_animated)
+            .and_then(|transition| {
+                let from = match single_pixel {
+                    Some((r, g, b)) => {
+                        let mut from = vec![0; frame.len()];
+                        let solid = RgbaImage::from_pixel(size.0, size.1, Rgba([r, g, b, 255]));
+                        write_bgra(&solid, &mut from);
+                        from
+                    }
+                    None => buffers.front()?.to_vec(),
+                };
+                Some((transition, from))
+            });
+        if from.is_none() {
+            if let Some(back) = buffers.back_mut()? {
+                write_bgra(&frame, back);
+                surface.animation = None;
+                surface.present(&self.qh);
+                drop(replaced);
+                return Ok(());
+            }
+        }
+
+        // Transitions blend in buffer order. Without one, the frame is shown as soon as a
+        // buffer is free.
+        let mut to = vec![0; frame.len()];
+        write_bgra(&frame, &mut to);
+        let (transition, from) = from.unwrap_or_default();
+        surface.animation = Some(Animation {
+            from,
+            to,
+            transition,
+            start: Instant::now(),
+        });
+        let result = self.animate(index);
+        drop(replaced);
+        result
+    }
+
+    /// Draws the next frame of a surface's transition, or its last one once the transition
+    /// has run its course.
+    fn animate(&mut self, index: usize) -> Result<()> {
+        let surface = &mut self.surfaces[index];
+        let (Some(animation), Some(buffers)) = (&surface.animation, &mut surface.buffers) else {
+            return Ok(());
+        };
+
+        let progress = match animation.transition.is_animated() {
+            true => animation.start.elapsed().as_secs_f64() / animation.transition.duration,
+            false => 1.0,
+        };
+        let (width, height) = buffers.size();
+        let Some(back) = buffers.back_mut()? else {
+            // The compositor still holds every buffer; try again on the next frame.
+            surface.surface.frame(&self.qh, surface.surface.clone());
+            surface.surface.commit();
+            return Ok(());
+        };
+        if progress >= 1.0 {
+            back.copy_from_slice(&animation.to);
+            surface.animation = None;
+        } else {
+            transition::blend(
+                &animation.transition,
+                progress,
+                &animation.from,
+                &animation.to,
+                back,
+                width,
+                height,
+            );
+        }
+        surface.present(&self.qh);
+        Ok(())
+    }
+}
+
+impl WallpaperSurface {
+    /// The name assignments are stored under.
+    fn key(&self) -> Option<&str> {
+        self.name.as_deref().or(self.description.as_deref())
+    }
+
+    fn matches(&self, output: &str) -> bool {
+        self.name.as_deref() == Some(output) || self.description.as_deref() == Some(output)
+    }
+
+    /// Attaches the frame drawn into the back buffer and commits it, asking for a frame
+    /// callback while a transition is running.
+    fn present(&mut self, qh: &QueueHandle<WallpaperState>) {
+        let Some(buffers) = self.buffers.as_mut() else {
+            return;
+        };
+        match &self.viewport {
+            Some(viewport) => viewport.set_destination(self.width as i32, self.height as i32),
+            None => self
+                .surface
+                .set_buffer_scale((self.scale / SCALE_DENOMINATOR) as i32),
+        }
+        buffers.attach(&self.surface);
+        if self.animation.is_some() {
+            self.surface.frame(qh, self.surface.clone());
+        }
+        self.surface.commit();
+        if let Some((single_pixel, _)) = self.single_pixel.take() {
+            single_pixel.destroy();
+        }
+    }
+
+    /// Size of the buffer in physical pixels. Without a viewport the buffer scale has to be
+    /// an integer, so fractional scales are rounded down.
+    fn buffer_size(&self) -> (u32, u32) {
+        let scale = match self.viewport {
+            Some(_) => self.scale,
+            None => (self.scale / SCALE_DENOMINATOR).max(1) * SCALE_DENOMINATOR,
+        };
+        (
+            physical_size(self.width, scale),
+            physical_size(self.height, scale),
+        )
+    }
+}
+
+impl Drop for WallpaperSurface {
+    fn drop(&mut self) {
+        if let Some(viewport) = self.viewport.take() {
+            viewport.destroy();
+        }
+        if let Some(fractional_scale) = self.fractional_scale.take() {
+            fractional_scale.destroy();
+        }
+        if let Some((single_pixel, _)) = self.single_pixel.take() {
+            single_pixel.destroy();
+        }
+    }
+}
+
+/// Converts a logical length to physical pixels at `scale` 120ths, rounding half away from
+/// zero as `wp_fractional_scale_v1` specifies.
+fn physical_size(logical: u32, scale: u32) -> u32 {
+    ((logical as u64 * scale as u64 + SCALE_DENOMINATOR as u64 / 2) / SCALE_DENOMINATOR as u64)
+        as u32
+}
+
+impl WallpaperState {
+    /// Records a new scale for a surface and redraws it at the new resolution.
+    fn set_scale(&mut self, index: usize, scale: u32) {
+        let surface = &mut self.surfaces[index];
+        if surface.scale == scale {
+            return;
+        }
+        surface.scale = scale;
+        if let Err(e) = self.draw(index, None) {
+            eprintln!("Warning: failed to draw wallpaper: {}", e);
+        }
+    }
+}
+
+impl CompositorHandler for WallpaperState {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        let Some(index) = self.surfaces.iter().position(|s| &s.surface == surface) else {
+            return;
+        };
+        // The fractional scale, when there is one, is more precise.
+        if self.surfaces[index].fractional_scale.is_none() {
+            self.set_scale(index, new_factor.max(1) as u32 * SCALE_DENOMINATOR);
+        }
+    }
+
+    /// Buffers are drawn upright at the configured size, which is already in the rotated
+    /// output's orientation, and the compositor applies the output transform.
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        _time: u32,
+    ) {
+        if let Some(index) = self.surfaces.iter().position(|s| &s.surface == surface) {
+            if let Err(e) = self.animate(index) {
+                eprintln!("Warning: failed to draw wallpaper: {}", e);
+            }
+        }
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for WallpaperState {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        self.add_surface(qh, output);
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        let Some(info) = self.output_state.info(&output) else {
+            return;
+        };
+        // The compositor closes layer surfaces of outputs it disables; give the output a new
+        // one once it is back.
+        let Some(index) = self.surfaces.iter().position(|s| s.output == output) else {
+            self.add_surface(qh, output);
+            return;
+        };
+
+        // A renamed output may have an image assigned under its new name. Mode and transform
+        // changes arrive as a new configure.
+        let surface = &mut self.surfaces[index];
+        surface.name = info.name.clone();
+        surface.description = info.description.clone();
+        if let Err(e) = self.draw(index, None) {
+            eprintln!("Warning: failed to draw wallpaper: {}", e);
+        }
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        // Dropping the surface destroys its buffers, pool and protocol objects. The output's
+        // assignment is kept for when it is plugged in again.
+        self.surfaces.retain(|s| s.output != output);
+    }
+}
+
+impl WallpaperState {
+    /// Creates the layer surface covering `output`. It is drawn once the compositor has
+    /// configured it.
+    fn add_surface(&mut self, qh: &QueueHandle<Self>, output: wl_output::WlOutput) {
+        let surface = self.compositor_state.create_surface(qh);
+        let layer_surface = self.layer_shell.create_layer_surface(
+            qh,
+            surface.clone(),
+            Layer::Background,
+            Some("wallrus"),
+            Some(&output),
+        );
+
+        // Cover the whole output, below everything, without taking input or space.
+        layer_surface.set_anchor(Anchor::all());
+        layer_surface.set_exclusive_zone(-1);
+        layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+
+        // Fractional scaling only helps when the buffer can be mapped to the logical size.
+        let (viewport, fractional_scale) = match (&self.viewporter, &self.fractional_scale_manager)
+        {
+            (Some(viewporter), Some(manager)) => match (viewporter.get(), manager.get()) {
+                (Ok(viewporter), Ok(manager)) => (
+                    Some(viewporter.get_viewport(&surface, qh, ())),
+                    Some(manager.get_fractional_scale(&surface, qh, ())),
+                ),
+                _ => (None, None),
+            },
+            _ => (None, None),
+        };
+
+        // The initial commit without a buffer makes the compositor send a configure.
+        surface.commit();
+
+        let info = self.output_state.info(&output);
+        self.surfaces.push(WallpaperSurface {
+            surface,
+            layer_surface,
+            output,
+            name: info.as_ref().and_then(|info| info.name.clone()),
+            description: info.and_then(|info| info.description),
+            width: 0,
+            height: 0,
+            scale: SCALE_DENOMINATOR,
+            viewport,
+            fractional_scale,
+            configured: false,
+            buffers: None,
+            single_pixel: None,
+            animation: None,
+            shown: None,
+        });
+    }
+}
+
+impl LayerShellHandler for WallpaperState {
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, layer: &LayerSurface) {
+        self.surfaces.retain(|s| &s.layer_surface != layer);
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        let Some(index) = self.surfaces.iter().position(|s| &s.layer_surface == layer) else {
+            return;
+        };
+
+        // Every configure is answered with a frame from the cached image: the first one, and
+        // later ones after hotplugging, mode changes or rotation. Nothing is redrawn if the
+        // size is unchanged.
+        let surface = &mut self.surfaces[index];
+        (surface.width, surface.height) = configure.new_size;
+        surface.configured = true;
+        if let Err(e) = self.draw(index, None) {
+            eprintln!("Warning: failed to draw wallpaper: {}", e);
+        }
+    }
+}
+
+impl ShmHandler for WallpaperState {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm_state
+    }
+}
+
+impl ProvidesRegistryState for WallpaperState {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+
+    registry_handlers![OutputState];
+}
+
+delegate_compositor!(WallpaperState);
+delegate_output!(WallpaperState);
+delegate_shm!(WallpaperState);
+delegate_layer!(WallpaperState);
+delegate_registry!(WallpaperState);
+delegate_simple!(WallpaperState, WpViewporter, 1);
+delegate_simple!(WallpaperState, WpFractionalScaleManagerV1, 1);
+delegate_simple!(WallpaperState, WpSinglePixelBufferManagerV1, 1);
+
+impl Dispatch<WpViewport, ()> for WallpaperState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WpViewport,
+        _event: <WpViewport as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        // wp_viewport has no events.
+    }
+}
+
+impl Dispatch<WpFractionalScaleV1, ()> for WallpaperState {
+    fn event(
+        state: &mut Self,
+        proxy: &WpFractionalScaleV1,
+        event: wp_fractional_scale_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let wp_fractional_scale_v1::Event::PreferredScale { scale } = event else {
+            return;
+        };
+        let Some(index) = state
+            .surfaces
+            .iter()
+            .position(|s| s.fractional_scale.as_ref() == Some(proxy))
+        else {
+            return;
+        };
+        state.set_scale(index, scale.max(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+    use wayland_client::{globals::registry_queue_init, EventQueue};
+    use wayland_protocols_wlr::layer_shell::v1::server::{
+        zwlr_layer_shell_v1::Layer as ServerLayer,
+        zwlr_layer_surface_v1::{
+            Anchor as ServerAnchor, KeyboardInteractivity as ServerKeyboardInteractivity,
+        },
+    };
+
+    use super::super::test_compositor::TestCompositor;
+    use super::*;
+
+    fn connect(compositor: &TestCompositor) -> (EventQueue<WallpaperState>, WallpaperState) {
+        let (globals, queue) = registry_queue_init(&compositor.connect()).unwrap();
+        let state = WallpaperState::new(&globals, &queue.handle()).unwrap();
+        let mut client = (queue, state);
+        settle(&mut client);
+        client
+    }
+
+    /// Lets requests and events go back and forth until everything pending is handled: the
+    /// outputs, the configures and the commits that answer them.
+    fn settle((queue, state): &mut (EventQueue<WallpaperState>, WallpaperState)) {
+        for _ in 0..4 {
+            queue.roundtrip(state).unwrap();
+        }
+    }
+
+    fn image() -> Wallpaper {
+        Wallpaper::Image {
+            path: PathBuf::from("/tmp/test.png"),
+            image: RgbaImage::from_fn(4, 2, |x, y| Rgba([x as u8 * 60, y as u8 * 200, 50, 255])),
+            placement: Placement::default(),
+        }
+    }
+
+    #[test]
+    fn test_creates_background_surface_per_output() {
+        let compositor = TestCompositor::new(&[("DP-1", 64, 32), ("HDMI-A-1", 32, 32)]);
+        let (_queue, state) = connect(&compositor);
+
+        let surfaces = compositor.layer_surfaces();
+        let outputs: Vec<_> = surfaces.iter().map(|s| s.output.as_deref()).collect();
+        assert_eq!(outputs, [Some("DP-1"), Some("HDMI-A-1")]);
+        for surface in &surfaces {
+            assert_eq!(surface.layer, ServerLayer::Background);
+            assert_eq!(surface.namespace, "wallrus");
+            assert_eq!(surface.anchor, ServerAnchor::all());
+            assert_eq!(surface.exclusive_zone, -1);
+            assert_eq!(
+                surface.keyboard_interactivity,
+                ServerKeyboardInteractivity::None
+            );
+        }
+        let names: Vec<_> = state.outputs().into_iter().map(|o| o.name).collect();
+        assert_eq!(names, [Some("DP-1".into()), Some("HDMI-A-1".into())]);
+    }
+
+    #[test]
+    fn test_draws_scaled_image_on_every_output() {
+        let compositor = TestCompositor::new(&[("DP-1", 64, 32), ("HDMI-A-1", 32, 32)]);
+        let mut client = connect(&compositor);
+        let wallpaper = image();
+        let Wallpaper::Image {
+            image, placement, ..
+        } = &wallpaper
+        else {
+            unreachable!()
+        };
+        let expected = [
+            ("DP-1", scaling::render(image, 64, 32, placement)),
+            ("HDMI-A-1", scaling::render(image, 32, 32, placement)),
+        ];
+
+        client
+            .1
+            .set_wallpaper(wallpaper, None, Transition::default())
+            .unwrap();
+        settle(&mut client);
+        for (output, expected) in expected {
+            assert!(compositor.frame(output) == Some(expected), "{}", output);
+        }
+
+        // A color on one output leaves the other alone.
+        client
+            .1
+            .set_wallpaper(
+                Wallpaper::Paint(Paint::Color((1, 2, 3))),
+                Some("HDMI-A-1"),
+                Transition::default(),
+            )
+            .unwrap();
+        settle(&mut client);
+        let color = compositor.frame("HDMI-A-1").unwrap();
+        assert!(color.pixels().all(|p| p == &Rgba([1, 2, 3, 255])));
+        assert_eq!(compositor.frame("DP-1").unwrap().get_pixel(0, 0)[2], 50);
+    }
+
+    #[test]
+    fn test_hotplugged_output_gets_cached_image() {
+        let compositor = TestCompositor::new(&[("DP-1", 16, 16)]);
+        let mut client = connect(&compositor);
+        client
+            .1
+            .set_wallpaper(image(), None, Transition::default())
+            .unwrap();
+        settle(&mut client);
+
+        compositor.add_output("DP-2", 8, 4);
+        settle(&mut client);
+        assert_eq!(compositor.layer_surfaces().len(), 2);
+        let frame = compositor
+            .frame("DP-2")
+            .expect("no frame on the new output");
+        assert_eq!(frame.dimensions(), (8, 4));
+
+        compositor.remove_output("DP-1");
+        settle(&mut client);
+        let outputs: Vec<_> = compositor
+            .layer_surfaces()
+            .into_iter()
+            .map(|s| s.output)
+            .collect();
+        assert_eq!(outputs, [Some("DP-2".into())]);
+        assert_eq!(client.1.outputs().len(), 1);
+    }
+
+    #[test]
+    fn test_closed_surface_is_destroyed() {
+        let compositor = TestCompositor::new(&[("DP-1", 16, 16), ("DP-2", 16, 16)]);
+        let mut client = connect(&compositor);
+
+        compositor.close_layer_surfaces("DP-1");
+        settle(&mut client);
+        let outputs: Vec<_> = compositor
+            .layer_surfaces()
+            .into_iter()
+            .map(|s| s.output)
+            .collect();
+        assert_eq!(outputs, [Some("DP-2".into())]);
+        let names: Vec<_> = client.1.outputs().into_iter().map(|o| o.name).collect();
+        assert_eq!(names, [Some("DP-2".into())]);
+    }
+
+    #[test]
+    fn test_physical_size_rounds_half_away_from_zero() {
+        assert_eq!(physical_size(1920, 120), 1920);
+        assert_eq!(physical_size(1280, 180), 1920);
+        assert_eq!(physical_size(1707, 180), 2561);
+        // 1.25 * 1 = 1.25 rounds down, 1.5 * 1 rounds up.
+        assert_eq!(physical_size(1, 150), 1);
+        assert_eq!(physical_size(1, 180), 2);
+    }
+}
@@ -0,0 +1,623 @@
+//! An in-process compositor for testing the wallpaper daemon without a display.
+//!
+//! It advertises `wl_compositor`, `wl_shm`, `wl_output` and `zwlr_layer_shell_v1`, answers
+//! the first commit of a layer surface with a configure at its output's size and keeps a
+//! copy of the last buffer committed to every surface. Outputs can be plugged in and out and
+//! layer surfaces closed while a client is connected. Requests are dispatched on a
+//! background thread, so clients can block on roundtrips.
+
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use image::RgbaImage;
+use wayland_client::Connection;
+use wayland_protocols_wlr::layer_shell::v1::server::{
+    zwlr_layer_shell_v1::{self, Layer, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, Anchor, KeyboardInteractivity, ZwlrLayerSurfaceV1},
+};
+use wayland_server::{
+    backend::{ClientData, ClientId, GlobalId},
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_callback::WlCallback,
+        wl_compositor::{self, WlCompositor},
+        wl_output::{self, WlOutput},
+        wl_region::{self, WlRegion},
+        wl_shm::{self, WlShm},
+        wl_shm_pool::{self, WlShmPool},
+        wl_surface::{self, WlSurface},
+    },
+    Client, DataInit, Dispatch, Display, DisplayHandle, GlobalDispatch, New, Resource,
+};
+
+pub struct TestCompositor {
+    inner: Arc<Mutex<Inner>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Inner {
+    display: Display<Server>,
+    server: Server,
+    layer_shell: Option<GlobalId>,
+}
+
+/// A layer surface as the client set it up, with the last frame it committed.
+#[derive(Debug, Clone)]
+pub struct LayerSurfaceInfo {
+    pub output: Option<String>,
+    pub layer: Layer,
+    pub namespace: String,
+    pub anchor: Anchor,
+    pub exclusive_zone: i32,
+    pub keyboard_interactivity: KeyboardInteractivity,
+    pub frame: Option<RgbaImage>,
+}
+
+#[derive(Default)]
+pub struct Server {
+    outputs: Vec<Output>,
+    surfaces: Vec<Surface>,
+    serial: u32,
+}
+
+struct Output {
+    name: String,
+    width: u32,
+    height: u32,
+    global: GlobalId,
+}
+
+struct Surface {
+    surface: WlSurface,
+    attached: Option<WlBuffer>,
+    callbacks: Vec<WlCallback>,
+    role: Option<LayerRole>,
+    frame: Option<RgbaImage>,
+}
+
+struct LayerRole {
+    resource: ZwlrLayerSurfaceV1,
+    info: LayerSurfaceInfo,
+    configured: bool,
+}
+
+/// An SHM buffer: where its pixels are in the pool's file.
+struct ShmBuffer {
+    file: Arc<File>,
+    offset: u64,
+    width: u32,
+    height: u32,
+    stride: u32,
+}
+
+struct ClientState;
+
+impl ClientData for ClientState {}
+
+impl TestCompositor {
+    /// Starts a compositor with outputs of the given names and sizes.
+    pub fn new(outputs: &[(&str, u32, u32)]) -> Self {
+        let display = Display::<Server>::new().expect("failed to create display");
+        let handle = display.handle();
+        handle.create_global::<Server, WlCompositor, ()>(4, ());
+        handle.create_global::<Server, WlShm, ()>(1, ());
+        let layer_shell = handle.create_global::<Server, ZwlrLayerShellV1, ()>(4, ());
+
+        let inner = Arc::new(Mutex::new(Inner {
+            display,
+            server: Server::default(),
+            layer_shell: Some(layer_shell),
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (inner, stop) = (inner.clone(), stop.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    {
+                        let mut inner = inner.lock().unwrap();
+                        let Inner {
+                            display, server, ..
+                        } = &mut *inner;
+                        display.dispatch_clients(server).unwrap();
+                        display.flush_clients().unwrap();
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                }
+            })
+        };
+
+        let compositor = Self {
+            inner,
+            stop,
+            thread: Some(thread),
+        };
+        for (name, width, height) in outputs {
+            compositor.add_output(name, *width, *height);
+        }
+        compositor
+    }
+
+    /// Connects a new client.
+    pub fn connect(&self) -> Connection {
+        let (client, server) = UnixStream::pair().unwrap();
+        self.inner
+            .lock()
+            .unwrap()
+            .display
+            .handle()
+            .insert_client(server, Arc::new(ClientState))
+            .unwrap();
+        Connection::from_socket(client).unwrap()
+    }
+
+    /// Plugs in an output.
+    pub fn add_output(&self, name: &str, width: u32, height: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        let global = inner
+            .display
+            .handle()
+            .create_global::<Server, WlOutput, String>(4, name.to_string());
+        inner.server.outputs.push(Output {
+            name: name.to_string(),
+            width,
+            height,
+            global,
+        });
+    }
+
+    /// Unplugs an output.
+    pub fn remove_output(&self, name: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(index) = inner.server.outputs.iter().position(|o| o.name == name) else {
+            return;
+        };
+        let output = inner.server.outputs.remove(index);
+        inner
+            .display
+            .handle()
+            .remove_global::<Server>(output.global);
+    }
+
+    /// Stops advertising `zwlr_layer_shell_v1`, like compositors without it.
+    pub fn remove_layer_shell(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(global) = inner.layer_shell.take() {
+            inner.display.handle().remove_global::<Server>(global);
+        }
+    }
+
+    /// Closes the layer surfaces on an output, as compositors do when they disable it.
+    pub fn close_layer_surfaces(&self, output: &str) {
+        let inner = self.inner.lock().unwrap();
+        for surface in &inner.server.surfaces {
+            if let Some(role) = &surface.role {
+                if role.info.output.as_deref() == Some(output) {
+                    role.resource.closed();
+                }
+            }
+        }
+    }
+
+    /// The layer surfaces that have not been destroyed, in order of creation.
+    pub fn layer_surfaces(&self) -> Vec<LayerSurfaceInfo> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .server
+            .surfaces
+            .iter()
+            .filter_map(|surface| {
+                let role = surface.role.as_ref()?;
+                Some(LayerSurfaceInfo {
+                    frame: surface.frame.clone(),
+                    ..role.info.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// The last frame committed on the layer surface of an output.
+    pub fn frame(&self, output: &str) -> Option<RgbaImage> {
+        self.layer_surfaces()
+            .into_iter()
+            .find(|surface| surface.output.as_deref() == Some(output))?
+            .frame
+    }
+}
+
+impl Drop for TestCompositor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Server {
+    fn surface(&mut self, surface: &WlSurface) -> Option<&mut Surface> {
+        self.surfaces.iter_mut().find(|s| &s.surface == surface)
+    }
+
+    /// Applies a commit: configures a new layer surface, copies the attached buffer and
+    /// releases it, and fires the frame callbacks.
+    fn commit(&mut self, surface: &WlSurface) {
+        self.serial += 1;
+        let serial = self.serial;
+        let outputs = &self.outputs;
+        let Some(state) = self.surfaces.iter_mut().find(|s| &s.surface == surface) else {
+            return;
+        };
+
+        if let Some(role) = state.role.as_mut().filter(|role| !role.configured) {
+            let size = role
+                .info
+                .output
+                .as_ref()
+                .and_then(|name| outputs.iter().find(|o| &o.name == name))
+                .map_or((0, 0), |o| (o.width, o.height));
+            role.resource.configure(serial, size.0, size.1);
+            role.configured = true;
+        }
+
+        if let Some(buffer) = state.attached.take() {
+            if let Some(shm) = buffer.data::<ShmBuffer>() {
+                state.frame = Some(shm.read());
+            }
+            buffer.release();
+        }
+        for callback in state.callbacks.drain(..) {
+            callback.done(serial);
+        }
+    }
+}
+
+impl ShmBuffer {
+    /// The buffer's Argb8888 pixels as an RGBA image.
+    fn read(&self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        let row_len = self.width as usize * 4;
+        for (y, row) in image.chunks_exact_mut(row_len).enumerate() {
+            let offset = self.offset + y as u64 * self.stride as u64;
+            self.file.read_exact_at(row, offset).unwrap();
+            for pixel in row.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        image
+    }
+}
+
+impl GlobalDispatch<WlCompositor, ()> for Server {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WlCompositor>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<WlCompositor, ()> for Server {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &WlCompositor,
+        request: wl_compositor::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_compositor::Request::CreateSurface { id } => {
+                let surface = data_init.init(id, ());
+                state.surfaces.push(Surface {
+                    surface,
+                    attached: None,
+                    callbacks: Vec::new(),
+                    role: None,
+                    frame: None,
+                });
+            }
+            wl_compositor::Request::CreateRegion { id } => {
+                data_init.init(id, ());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlSurface, ()> for Server {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        resource: &WlSurface,
+        request: wl_surface::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_surface::Request::Attach { buffer, .. } => {
+                if let Some(surface) = state.surface(resource) {
+                    surface.attached = buffer;
+                }
+            }
+            wl_surface::Request::Frame { callback } => {
+                let callback = data_init.init(callback, ());
+                if let Some(surface) = state.surface(resource) {
+                    surface.callbacks.push(callback);
+                }
+            }
+            wl_surface::Request::Commit => state.commit(resource),
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut Self, _client: ClientId, resource: &WlSurface, _data: &()) {
+        state.surfaces.retain(|s| &s.surface != resource);
+    }
+}
+
+impl Dispatch<WlRegion, ()> for Server {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlRegion,
+        _request: wl_region::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlCallback, ()> for Server {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlCallback,
+        _request: <WlCallback as Resource>::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<WlShm, ()> for Server {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WlShm>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let shm = data_init.init(resource, ());
+        shm.format(wl_shm::Format::Argb8888);
+        shm.format(wl_shm::Format::Xrgb8888);
+    }
+}
+
+impl Dispatch<WlShm, ()> for Server {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlShm,
+        request: wl_shm::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_shm::Request::CreatePool { id, fd, .. } = request {
+            data_init.init(id, Arc::new(File::from(fd)));
+        }
+    }
+}
+
+impl Dispatch<WlShmPool, Arc<File>> for Server {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlShmPool,
+        request: wl_shm_pool::Request,
+        file: &Arc<File>,
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        // Pixels are read from the file on commit, so resizing needs no remapping.
+        if let wl_shm_pool::Request::CreateBuffer {
+            id,
+            offset,
+            width,
+            height,
+            stride,
+            ..
+        } = request
+        {
+            data_init.init(
+                id,
+                ShmBuffer {
+                    file: file.clone(),
+                    offset: offset as u64,
+                    width: width as u32,
+                    height: height as u32,
+                    stride: stride as u32,
+                },
+            );
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, ShmBuffer> for Server {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlBuffer,
+        _request: wl_buffer::Request,
+        _data: &ShmBuffer,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<WlOutput, String> for Server {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WlOutput>,
+        name: &String,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let output = data_init.init(resource, name.clone());
+        let Some(info) = state.outputs.iter().find(|o| &o.name == name) else {
+            return;
+        };
+        output.geometry(
+            0,
+            0,
+            0,
+            0,
+            wl_output::Subpixel::Unknown,
+            "Wallrus".into(),
+            "Test".into(),
+            wl_output::Transform::Normal,
+        );
+        output.mode(
+            wl_output::Mode::Current,
+            info.width as i32,
+            info.height as i32,
+            60_000,
+        );
+        output.scale(1);
+        output.name(name.clone());
+        output.description(format!("Wallrus Test {}", name));
+        output.done();
+    }
+}
+
+impl Dispatch<WlOutput, String> for Server {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlOutput,
+        _request: wl_output::Request,
+        _data: &String,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<ZwlrLayerShellV1, ()> for Server {
+    fn bind(
+        _state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrLayerShellV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrLayerShellV1, ()> for Server {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrLayerShellV1,
+        request: zwlr_layer_shell_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwlr_layer_shell_v1::Request::GetLayerSurface {
+            id,
+            surface,
+            output,
+            layer,
+            namespace,
+        } = request
+        {
+            let resource = data_init.init(id, surface.clone());
+            let info = LayerSurfaceInfo {
+                output: output.and_then(|output| output.data::<String>().cloned()),
+                layer: layer.into_result().unwrap_or(Layer::Background),
+                namespace,
+                anchor: Anchor::empty(),
+                exclusive_zone: 0,
+                keyboard_interactivity: KeyboardInteractivity::None,
+                frame: None,
+            };
+            if let Some(surface) = state.surface(&surface) {
+                surface.role = Some(LayerRole {
+                    resource,
+                    info,
+                    configured: false,
+                });
+            }
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, WlSurface> for Server {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZwlrLayerSurfaceV1,
+        request: zwlr_layer_surface_v1::Request,
+        surface: &WlSurface,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        let Some(info) = state
+            .surface(surface)
+            .and_then(|surface| surface.role.as_mut())
+            .map(|role| &mut role.info)
+        else {
+            return;
+        };
+        match request {
+            zwlr_layer_surface_v1::Request::SetAnchor { anchor } => {
+                info.anchor = anchor.into_result().unwrap_or(Anchor::empty());
+            }
+            zwlr_layer_surface_v1::Request::SetExclusiveZone { zone } => {
+                info.exclusive_zone = zone;
+            }
+            zwlr_layer_surface_v1::Request::SetKeyboardInteractivity {
+                keyboard_interactivity,
+            } => {
+                info.keyboard_interactivity = keyboard_interactivity
+                    .into_result()
+                    .unwrap_or(KeyboardInteractivity::None);
+            }
+            zwlr_layer_surface_v1::Request::SetLayer { layer } => {
+                info.layer = layer.into_result().unwrap_or(Layer::Background);
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut Self,
+        _client: ClientId,
+        _resource: &ZwlrLayerSurfaceV1,
+        surface: &WlSurface,
+    ) {
+        if let Some(surface) = state.surface(surface) {
+            surface.role = None;
+        }
+    }
+}
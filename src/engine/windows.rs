@@ -1,12 +1,20 @@
+use crate::engine::{Placement, ScaleMode, Transition};
 use crate::errors::{Result, WallrusError};
+use crate::utils::run;
 use std::ffi::OsStr;
 use std::os::windows::ffi::OsStrExt;
 use std::path::Path;
+use std::process::Command;
 use winapi::um::winuser::{
     SystemParametersInfoW, SPIF_SENDCHANGE, SPIF_UPDATEINIFILE, SPI_SETDESKWALLPAPER,
 };
 
-pub fn set_wallpaper(image_path: &Path) -> Result<()> {
+/// Sets the wallpaper and its style. Windows does not animate the change.
+pub fn set_wallpaper(
+    image_path: &Path,
+    placement: &Placement,
+    _transition: &Transition,
+) -> Result<()> {
     if !image_path.exists() {
         return Err(WallrusError::Config(format!(
             "Wallpaper file does not exist: {:?}",
@@ -14,6 +22,8 @@ pub fn set_wallpaper(image_path: &Path) -> Result<()> {
         )));
     }
 
+    set_wallpaper_style(placement.mode)?;
+
     let wide_path: Vec<u16> = OsStr::new(image_path)
         .encode_wide()
         .chain(std::iter::once(0))
@@ -34,3 +44,28 @@ pub fn set_wallpaper(image_path: &Path) -> Result<()> {
 
     Ok(())
 }
+
+/// Stores the scaling mode in the registry; it takes effect with the next wallpaper change.
+fn set_wallpaper_style(mode: ScaleMode) -> Result<()> {
+    let (style, tile) = match mode {
+        ScaleMode::Fill => ("10", "0"),
+        ScaleMode::Fit => ("6", "0"),
+        ScaleMode::Stretch => ("2", "0"),
+        ScaleMode::Center => ("0", "0"),
+        ScaleMode::Tile => ("0", "1"),
+    };
+    for (name, value) in [("WallpaperStyle", style), ("TileWallpaper", tile)] {
+        run(Command::new("reg").args([
+            "add",
+            r"HKCU\Control Panel\Desktop",
+            "/v",
+            name,
+            "/t",
+            "REG_SZ",
+            "/d",
+            value,
+            "/f",
+        ]))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Stand-ins for the features of the native Wayland backend when it is not compiled in.
+
+#[cfg(not(target_os = "linux"))]
+use std::path::Path;
+
+#[cfg(not(target_os = "linux"))]
+use super::Placement;
+use super::{OutputStatus, Paint, Transition};
+use crate::errors::{Result, WallrusError};
+
+fn unsupported<T>() -> Result<T> {
+    Err(WallrusError::Config(
+        "This needs the native Wayland backend. Compile with --features wayland".into(),
+    ))
+}
+
+pub fn run_daemon() -> Result<()> {
+    unsupported()
+}
+
+pub fn stop_daemon() -> Result<()> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_output_wallpaper(
+    _image_path: &Path,
+    _output: &str,
+    _placement: &Placement,
+    _transition: &Transition,
+) -> Result<()> {
+    unsupported()
+}
+
+pub fn set_output_paint(_paint: &Paint, _output: &str, _transition: &Transition) -> Result<()> {
+    unsupported()
+}
+
+pub fn list_outputs() -> Result<Vec<OutputStatus>> {
+    unsupported()
+}
@@ -14,6 +14,10 @@ pub enum WallrusError {
 
     #[error("Image processing error: {0}")]
     ImageProcessing(String),
+
+    /// An external command or service that sets the wallpaper reported an error.
+    #[error("{command} failed: {message}")]
+    Backend { command: String, message: String },
 }
 
 pub type Result<T> = std::result::Result<T, WallrusError>;
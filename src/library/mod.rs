@@ -0,0 +1,285 @@
+//! Persistent index of wallpaper metadata (palette, brightness, rating and display history),
+//! keyed by image path.
+
+mod query;
+
+pub use query::{ColorQuery, Tone};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::palette::{extract_palette_from_file, Palette};
+use crate::errors::{Result, WallrusError};
+use crate::utils::{data_dir, write_atomic};
+
+/// Number of dominant colors stored per image.
+pub const PALETTE_SIZE: usize = 5;
+
+const LIBRARY_FILE: &str = "library.json";
+
+/// Ratings and display history live next to the library in this smaller file, so showing
+/// an image does not rewrite every palette.
+const STATS_FILE: &str = "stats.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    /// Modification time of the image when it was analyzed, in seconds since the epoch.
+    pub modified: u64,
+    pub palette: Palette,
+}
+
+/// Highest rating accepted by `Library::set_rating`.
+pub const MAX_RATING: u8 = 5;
+
+/// User-facing bookkeeping that does not require analyzing the image.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageStats {
+    pub rating: Option<u8>,
+    /// When the image was last set as wallpaper, in seconds since the epoch.
+    pub last_shown: Option<u64>,
+    pub times_shown: u32,
+}
+
+/// Stats changed by this process since the last save, applied on top of what is on disk
+/// then so that other processes' changes are kept.
+#[derive(Debug, Default)]
+struct StatsChange {
+    rating: Option<u8>,
+    times_shown: u32,
+    last_shown: Option<u64>,
+}
+
+/// The library, shared with other wallrus processes (a running slideshow and `wallrus
+/// library rate`, say). Each save reloads the files and merges in only this process's changes.
+#[derive(Debug, Default)]
+pub struct Library {
+    path: PathBuf,
+    stats_path: PathBuf,
+    entries: BTreeMap<PathBuf, LibraryEntry>,
+    stats: BTreeMap<PathBuf, ImageStats>,
+    /// Images analyzed since the last save
+    analyzed: BTreeSet<PathBuf>,
+    changed: BTreeMap<PathBuf, StatsChange>,
+}
+
+/// Reads a JSON file, `None` if it does not exist.
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read_to_string(path)?;
+    serde_json::from_str(&data)
+        .map(Some)
+        .map_err(|e| WallrusError::Config(format!("Failed to parse library {:?}: {}", path, e)))
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let data = serde_json::to_string_pretty(value)
+        .map_err(|e| WallrusError::Config(format!("Failed to serialize library: {}", e)))?;
+    write_atomic(path, data.as_bytes())
+}
+
+impl Library {
+    /// Loads the library from the user data directory, starting empty if none exists yet.
+    pub fn load() -> Result<Self> {
+        Self::load_from(&data_dir()?.join(LIBRARY_FILE))
+    }
+
+    /// Loads the library at `path`, with its stats in `stats.json` next to it.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let stats_path = path.with_file_name(STATS_FILE);
+        Ok(Self {
+            entries: read_json(path)?.unwrap_or_default(),
+            stats: read_json(&stats_path)?.unwrap_or_default(),
+            path: path.to_path_buf(),
+            stats_path,
+            ..Default::default()
+        })
+    }
+
+    /// Writes this process's changes, merged into the files as they are now. Palettes are
+    /// only written when images were analyzed.
+    pub fn save(&mut self) -> Result<()> {
+        if !self.analyzed.is_empty() {
+            let mut entries: BTreeMap<PathBuf, LibraryEntry> =
+                read_json(&self.path)?.unwrap_or_default();
+            for key in mem::take(&mut self.analyzed) {
+                if let Some(entry) = self.entries.get(&key) {
+                    entries.insert(key, entry.clone());
+                }
+            }
+            write_json(&self.path, &entries)?;
+            self.entries = entries;
+        }
+        if !self.changed.is_empty() {
+            let mut stats: BTreeMap<PathBuf, ImageStats> =
+                read_json(&self.stats_path)?.unwrap_or_default();
+            for (key, change) in mem::take(&mut self.changed) {
+                let stats = stats.entry(key).or_default();
+                if change.rating.is_some() {
+                    stats.rating = change.rating;
+                }
+                stats.times_shown += change.times_shown;
+                stats.last_shown = stats.last_shown.max(change.last_shown);
+            }
+            write_json(&self.stats_path, &stats)?;
+            self.stats = stats;
+        }
+        Ok(())
+    }
+
+    /// Returns the entry for an image, analyzing it first if it is new or has changed on disk.
+    pub fn entry(&mut self, image: &Path) -> Result<&LibraryEntry> {
+        let key = image.canonicalize()?;
+        let modified = modified_secs(&key)?;
+
+        let stale = self
+            .entries
+            .get(&key)
+            .is_none_or(|entry| entry.modified != modified);
+        if stale {
+            let palette = extract_palette_from_file(&key, PALETTE_SIZE)?;
+            self.entries
+                .insert(key.clone(), LibraryEntry { modified, palette });
+            self.analyzed.insert(key.clone());
+        }
+
+        Ok(&self.entries[&key])
+    }
+
+    /// Returns the rating and display history of an image; unknown images get empty stats.
+    pub fn stats(&self, image: &Path) -> ImageStats {
+        image
+            .canonicalize()
+            .ok()
+            .and_then(|key| self.stats.get(&key).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn set_rating(&mut self, image: &Path, rating: u8) -> Result<()> {
+        if rating > MAX_RATING {
+            return Err(WallrusError::Config(format!(
+                "Rating must be between 0 and {}",
+                MAX_RATING
+            )));
+        }
+        let key = image.canonicalize()?;
+        self.stats.entry(key.clone()).or_default().rating = Some(rating);
+        self.changed.entry(key).or_default().rating = Some(rating);
+        Ok(())
+    }
+
+    /// Records that an image was just set as wallpaper.
+    pub fn record_shown(&mut self, image: &Path) -> Result<()> {
+        let key = image.canonicalize()?;
+        let now = Some(unix_now());
+        let stats = self.stats.entry(key.clone()).or_default();
+        stats.last_shown = now;
+        stats.times_shown += 1;
+        let change = self.changed.entry(key).or_default();
+        change.last_shown = now;
+        change.times_shown += 1;
+        Ok(())
+    }
+
+    /// Analyzes every image that is not indexed yet, showing progress. Returns the number of
+    /// images that could not be analyzed.
+    pub fn index(&mut self, images: &[PathBuf]) -> Result<usize> {
+        let pb = ProgressBar::new(images.len() as u64);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+                )
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+
+        let mut failed = 0;
+        for image in images {
+            if let Err(e) = self.entry(image) {
+                pb.println(format!("Skipping {:?}: {}", image, e));
+                failed += 1;
+            }
+            pb.inc(1);
+        }
+        pb.finish_and_clear();
+
+        self.save()?;
+        Ok(failed)
+    }
+
+    /// Returns the images matching `query`, closest color match first.
+    pub fn filter(&mut self, images: &[PathBuf], query: &ColorQuery) -> Result<Vec<PathBuf>> {
+        if query.is_empty() {
+            return Ok(images.to_vec());
+        }
+
+        self.index(images)?;
+
+        let mut matches: Vec<(f32, PathBuf)> = Vec::new();
+        for image in images {
+            let Ok(entry) = self.entry(image) else {
+                continue;
+            };
+            if let Some(score) = query.score(&entry.palette) {
+                matches.push((score, image.clone()));
+            }
+        }
+        matches.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        Ok(matches.into_iter().map(|(_, path)| path).collect())
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn modified_secs(path: &Path) -> Result<u64> {
+    Ok(fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_keeps_changes_of_other_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LIBRARY_FILE);
+        let image = dir.path().join("a.png");
+        image::RgbaImage::new(2, 2).save(&image).unwrap();
+
+        // A slideshow loads the library, then `library rate` runs while it shows images.
+        let mut slideshow = Library::load_from(&path).unwrap();
+        let mut rate = Library::load_from(&path).unwrap();
+        rate.set_rating(&image, 4).unwrap();
+        rate.save().unwrap();
+        slideshow.record_shown(&image).unwrap();
+        slideshow.save().unwrap();
+        assert!(!path.exists());
+
+        let stats = Library::load_from(&path).unwrap().stats(&image);
+        assert_eq!(stats.rating, Some(4));
+        assert_eq!(stats.times_shown, 1);
+        assert_eq!(slideshow.stats(&image).rating, Some(4));
+
+        slideshow.entry(&image).unwrap();
+        slideshow.save().unwrap();
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::engine::color::{delta_e, rgb_to_lab, Rgb};
+use crate::engine::palette::Palette;
+
+/// Palette colors covering less of the image than this are ignored when matching.
+const MIN_COLOR_WEIGHT: f32 = 0.05;
+
+/// Average brightness below which an image counts as dark, and above which (mirrored) as light.
+const DARK_THRESHOLD: f32 = 0.35;
+const LIGHT_THRESHOLD: f32 = 0.65;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Dark,
+    Light,
+}
+
+/// Selects images by dominant color and/or overall brightness.
+#[derive(Debug, Clone, Default)]
+pub struct ColorQuery {
+    pub color: Option<Rgb>,
+    pub tone: Option<Tone>,
+    /// Maximum perceptual distance (CIE76 delta E) for a color match.
+    pub tolerance: f32,
+}
+
+impl ColorQuery {
+    pub fn is_empty(&self) -> bool {
+        self.color.is_none() && self.tone.is_none()
+    }
+
+    /// Returns `None` if the palette does not match, otherwise a score where lower is a
+    /// closer match.
+    pub fn score(&self, palette: &Palette) -> Option<f32> {
+        match self.tone {
+            Some(Tone::Dark) if palette.brightness > DARK_THRESHOLD => return None,
+            Some(Tone::Light) if palette.brightness < LIGHT_THRESHOLD => return None,
+            _ => {}
+        }
+
+        let Some(color) = self.color else {
+            return Some(0.0);
+        };
+        let target = rgb_to_lab(color);
+
+        palette
+            .colors
+            .iter()
+            .filter(|c| c.weight >= MIN_COLOR_WEIGHT)
+            .map(|c| delta_e(rgb_to_lab(c.rgb), target))
+            .filter(|distance| *distance <= self.tolerance)
+            .min_by(|a, b| a.total_cmp(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::palette::PaletteColor;
+
+    fn palette(colors: &[(Rgb, f32)], brightness: f32) -> Palette {
+        Palette {
+            colors: colors
+                .iter()
+                .map(|&(rgb, weight)| PaletteColor { rgb, weight })
+                .collect(),
+            brightness,
+        }
+    }
+
+    #[test]
+    fn test_tone_filter() {
+        let dark = palette(&[((10, 10, 20), 1.0)], 0.1);
+        let light = palette(&[((240, 240, 230), 1.0)], 0.9);
+        let query = ColorQuery {
+            tone: Some(Tone::Dark),
+            ..Default::default()
+        };
+
+        assert!(query.score(&dark).is_some());
+        assert!(query.score(&light).is_none());
+    }
+
+    #[test]
+    fn test_color_match_prefers_closer_palette() {
+        let navy = palette(&[((30, 58, 95), 0.6), ((200, 200, 200), 0.4)], 0.4);
+        let blue = palette(&[((40, 70, 120), 0.8)], 0.4);
+        let red = palette(&[((200, 20, 20), 1.0)], 0.4);
+        let query = ColorQuery {
+            color: Some((0x1e, 0x3a, 0x5f)),
+            tolerance: 25.0,
+            ..Default::default()
+        };
+
+        let navy_score = query.score(&navy).unwrap();
+        let blue_score = query.score(&blue).unwrap();
+        assert!(navy_score < blue_score);
+        assert!(query.score(&red).is_none());
+    }
+
+    #[test]
+    fn test_minor_colors_are_ignored() {
+        let speck = palette(&[((255, 255, 255), 0.98), ((30, 58, 95), 0.02)], 0.9);
+        let query = ColorQuery {
+            color: Some((30, 58, 95)),
+            tolerance: 25.0,
+            ..Default::default()
+        };
+
+        assert!(query.score(&speck).is_none());
+    }
+}
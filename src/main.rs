@@ -2,13 +2,17 @@ mod cli;
 mod config;
 mod engine;
 mod errors;
+mod library;
 mod providers;
 mod utils;
 
 use crate::cli::Cli;
-use crate::errors::Result;
-use config::config::Config;
-use std::time::Duration;
+use crate::engine::{Placement, Transition};
+use crate::errors::{Result, WallrusError};
+use crate::library::Library;
+use config::config::{Config, OutputWallpaper};
+use rand::seq::SliceRandom;
+use std::path::{Path, PathBuf};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -18,34 +22,321 @@ async fn main() -> Result<()> {
     // Parse command line arguments
     let cli = Cli::parse_args();
 
+    // Controlling a running slideshow, the daemon and diagnostics need no configuration
+    match cli.command {
+        cli::Commands::Doctor => return doctor(&cli.backends),
+        cli::Commands::Ctl { command, json } => return control_slideshow(command, json).await,
+        cli::Commands::Daemon { stop: true } => return engine::stop_daemon(),
+        cli::Commands::Daemon { stop: false } => return engine::run_daemon(),
+        _ => {}
+    }
+
     // Load and validate configuration
     let config = Config::load()?;
+    engine::prefer_backends(if cli.backends.is_empty() {
+        &config.backends
+    } else {
+        &cli.backends
+    })?;
 
     match cli.command {
         cli::Commands::Download {
             keyword,
             collection,
             artist,
+            placement,
+            transition,
         } => {
             println!("Downloading wallpaper...");
             providers::unsplash::download_and_set_wallpaper(
-                &config.unsplash_access_key,
+                config.unsplash_access_key()?,
                 keyword.as_deref(),
                 collection.as_deref(),
                 artist.as_deref(),
                 &config.image_path,
+                &placement.resolve(&config)?,
+                &transition.resolve(&config)?,
             )
             .await?;
         }
-        cli::Commands::Slideshow { interval } => {
+        cli::Commands::Set {
+            path,
+            random,
+            sources,
+            scan,
+            color,
+            paint,
+            placement,
+            transition,
+            output,
+            dark_image,
+        } => {
+            let image = match path {
+                Some(path) => path,
+                None if random => {
+                    let sources = sources_or_default(sources, &config);
+                    let images = utils::scan_images(&sources, &scan.options())?;
+                    let candidates = Library::load()?.filter(&images, &color.query()?)?;
+                    candidates
+                        .choose(&mut rand::thread_rng())
+                        .cloned()
+                        .ok_or_else(|| {
+                            WallrusError::Config("No matching images found in directory".into())
+                        })?
+                }
+                None => {
+                    let paint = paint.resolve(&color)?;
+                    println!("Setting wallpaper: {}", paint);
+                    let transition = transition.resolve(&config)?;
+                    engine::set_paint(&paint, output.as_deref(), &transition)?;
+                    return Ok(());
+                }
+            };
+            println!("Setting wallpaper: {:?}", image);
+            let placement = placement.resolve(&config)?;
+            let transition = transition.resolve(&config)?;
+            match (output, dark_image) {
+                (Some(output), _) => {
+                    engine::set_output_wallpaper(&image, &output, &placement, &transition)?
+                }
+                (None, Some(dark)) => {
+                    engine::set_light_dark_wallpaper(&image, &dark, &placement, &transition)?
+                }
+                (None, None) => engine::set_wallpaper(&image, &placement, &transition)?,
+            }
+        }
+        cli::Commands::Slideshow(args) => {
             println!("Starting slideshow...");
-            engine::create_slideshow(&config.image_path, Duration::from_secs(interval))?;
+            let options = args.resolve(&config)?;
+            engine::run_slideshow(options, || args.resolve(&Config::load()?)).await?;
         }
-        cli::Commands::Generate { width, height } => {
+        cli::Commands::Generate {
+            width,
+            height,
+            placement,
+            transition,
+        } => {
             println!("Generating wallpaper...");
-            engine::generate_wallpaper(width, height, &config.image_path)?;
+            let image = engine::generate_wallpaper(width, height, &config.image_path)?;
+            engine::set_wallpaper(
+                &image,
+                &placement.resolve(&config)?,
+                &transition.resolve(&config)?,
+            )?;
+        }
+        cli::Commands::Library { command } => match command {
+            cli::LibraryCommands::Index { sources, scan } => {
+                let sources = sources_or_default(sources, &config);
+                let images = utils::scan_images(&sources, &scan.options())?;
+                println!("Indexing {} images...", images.len());
+                let failed = Library::load()?.index(&images)?;
+                println!("Indexed {} images", images.len() - failed);
+            }
+            cli::LibraryCommands::Palette { path } => {
+                print_palette(&path)?;
+            }
+            cli::LibraryCommands::Rate { path, rating } => {
+                let mut library = Library::load()?;
+                library.set_rating(&path, rating)?;
+                library.save()?;
+                println!("Rated {:?} {}/{}", path, rating, library::MAX_RATING);
+            }
+        },
+        cli::Commands::Outputs {
+            apply,
+            placement,
+            transition,
+        } => {
+            if apply {
+                apply_output_config(
+                    &config,
+                    &placement.resolve(&config)?,
+                    &transition.resolve(&config)?,
+                )?;
+            }
+            print_outputs()?;
+        }
+        cli::Commands::Backends => print_backends(),
+        cli::Commands::Ctl { .. } | cli::Commands::Daemon { .. } | cli::Commands::Doctor => {
+            unreachable!("handled before loading the configuration")
+        }
+    }
+
+    Ok(())
+}
+
+async fn control_slideshow(command: cli::CtlCommands, json: bool) -> Result<()> {
+    let response = engine::control::send(command.control()).await?;
+    if json {
+        let output = serde_json::to_string_pretty(&response)
+            .map_err(|e| WallrusError::Config(format!("Failed to encode response: {}", e)))?;
+        println!("{}", output);
+    }
+    if let Some(error) = response.error {
+        return Err(WallrusError::Config(error));
+    }
+
+    if let (false, cli::CtlCommands::Status, Some(status)) = (json, command, response.status) {
+        match &status.current {
+            Some(current) => println!("Current: {}", current.display()),
+            None => println!("Current: none"),
         }
+        let state = match (status.paused, status.next_change) {
+            (true, _) => "paused".to_string(),
+            (false, Some(seconds)) => format!("next change in {}s", seconds),
+            (false, None) => "waiting for images".to_string(),
+        };
+        println!("State: {} (interval {}s)", state, status.interval);
+        println!("Images: {}", status.images);
     }
+    Ok(())
+}
 
+/// Falls back to the configured image directory when no sources are given.
+fn sources_or_default(sources: Vec<PathBuf>, config: &Config) -> Vec<PathBuf> {
+    if sources.is_empty() {
+        vec![PathBuf::from(&config.image_path)]
+    } else {
+        sources
+    }
+}
+
+/// Sets each output listed in the `[outputs]` table of the config file.
+fn apply_output_config(
+    config: &Config,
+    placement: &Placement,
+    transition: &Transition,
+) -> Result<()> {
+    if config.outputs.is_empty() {
+        return Err(WallrusError::Config(
+            "No [outputs] table in the config file".into(),
+        ));
+    }
+
+    let outputs = engine::list_outputs()?;
+    for (name, wallpaper) in &config.outputs {
+        let image = match wallpaper {
+            OutputWallpaper::Generate => {
+                // Generate at the output's own resolution.
+                let output = outputs
+                    .iter()
+                    .find(|output| {
+                        output.name.as_ref() == Some(name)
+                            || output.description.as_ref() == Some(name)
+                    })
+                    .ok_or_else(|| WallrusError::Config(format!("No output named {:?}", name)))?;
+                engine::generate_wallpaper(output.width, output.height, &config.image_path)?
+            }
+            OutputWallpaper::Path(path) if path.is_dir() => {
+                let images = utils::scan_images(std::slice::from_ref(path), &Default::default())?;
+                images
+                    .choose(&mut rand::thread_rng())
+                    .cloned()
+                    .ok_or_else(|| WallrusError::Config(format!("No images found in {:?}", path)))?
+            }
+            OutputWallpaper::Path(path) => path.clone(),
+        };
+        println!("{}: {:?}", name, image);
+        engine::set_output_wallpaper(&image, name, placement, transition)?;
+    }
+    Ok(())
+}
+
+fn print_outputs() -> Result<()> {
+    for output in engine::list_outputs()? {
+        println!(
+            "{} ({}x{}){}",
+            output.name.as_deref().unwrap_or("unnamed"),
+            output.width,
+            output.height,
+            output
+                .description
+                .map(|description| format!(" - {}", description))
+                .unwrap_or_default()
+        );
+        match (output.image, output.paint) {
+            (Some(image), _) => println!("    {}", image.display()),
+            (None, Some(paint)) => println!("    {}", paint),
+            (None, None) => println!("    no wallpaper"),
+        }
+    }
+    Ok(())
+}
+
+fn print_backends() {
+    let backends = engine::list_backends();
+    if backends.is_empty() {
+        println!("This platform has a single way of setting the wallpaper");
+    }
+    for (backend, available) in backends {
+        println!(
+            "{:<10} priority {:<4} {}",
+            backend.name(),
+            backend.priority(),
+            if available {
+                "available"
+            } else {
+                "unavailable"
+            }
+        );
+        if let Some(current) = available.then(|| backend.current()).flatten() {
+            println!("    {}", current.display());
+        }
+    }
+}
+
+/// Explains what was detected about the session, from which variables or processes, and
+/// which backends would be used.
+fn doctor(backends: &[String]) -> Result<()> {
+    let detection = engine::desktop::detected();
+    println!("Environment:");
+    for name in engine::desktop::VARIABLES {
+        match std::env::var(name) {
+            Ok(value) => println!("    {}={}", name, value),
+            Err(_) => println!("    {} is not set", name),
+        }
+    }
+    match &detection.desktop {
+        Some((desktop, reason)) => println!("Desktop: {} ({})", desktop, reason),
+        None => println!("Desktop: unknown (no variable or running process names one)"),
+    }
+    match &detection.session {
+        Some((session, reason)) => println!("Session: {} ({})", session, reason),
+        None => println!("Session: unknown (neither Wayland nor X11 is set up)"),
+    }
+    if let Some((version, reason)) = &detection.plasma_version {
+        println!("Plasma: {} ({})", version, reason);
+    }
+
+    let config_backends = match Config::load() {
+        Ok(config) => config.backends,
+        Err(e) => {
+            println!("Config: {}", e);
+            Vec::new()
+        }
+    };
+    engine::prefer_backends(if backends.is_empty() {
+        &config_backends
+    } else {
+        backends
+    })?;
+    println!("Backends, in the order they are tried:");
+    print_backends();
+    Ok(())
+}
+
+fn print_palette(path: &Path) -> Result<()> {
+    let mut library = Library::load()?;
+    let palette = library.entry(path)?.palette.clone();
+    library.save()?;
+
+    println!("Brightness: {:.2}", palette.brightness);
+    for color in &palette.colors {
+        println!(
+            "{}  {:>5.1}%",
+            engine::color::to_hex(color.rgb),
+            color.weight * 100.0
+        );
+    }
     Ok(())
 }
@@ -6,7 +6,7 @@ use serde_json::Value;
 use std::path::Path;
 use tokio::io::AsyncWriteExt;
 
-use crate::engine::set_wallpaper;
+use crate::engine::{set_wallpaper, Placement, Transition};
 use crate::utils::{generate_unique_filename, is_valid_file};
 
 const UNSPLASH_SEARCH_URL: &str = "https://api.unsplash.com/search/photos";
@@ -99,6 +99,8 @@ pub async fn download_and_set_wallpaper(
     collection: Option<&str>,
     artist: Option<&str>,
     image_path: &str,
+    placement: &Placement,
+    transition: &Transition,
 ) -> Result<()> {
     println!("Fetching image URL from Unsplash... ");
     let image_url = fetch_unsplash_image_url(access_key, query, collection, artist).await?;
@@ -114,7 +116,7 @@ pub async fn download_and_set_wallpaper(
     }
 
     let path = Path::new(&file_name);
-    set_wallpaper(path)?;
+    set_wallpaper(path, placement, transition)?;
 
     Ok(())
 }
@@ -0,0 +1,103 @@
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::errors::{Result, WallrusError};
+
+/// How long a helper such as `gsettings` or `swww` may take before it is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs `command` to completion and returns its standard output. A non-zero exit, or taking
+/// longer than [`COMMAND_TIMEOUT`], is a [`WallrusError::Backend`] carrying the command's
+/// standard error.
+pub fn run(command: &mut Command) -> Result<String> {
+    run_with_timeout(command, COMMAND_TIMEOUT)
+}
+
+fn run_with_timeout(command: &mut Command, timeout: Duration) -> Result<String> {
+    let name = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drain both pipes while waiting, so a chatty command cannot block on a full pipe.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let status = match wait(&mut child, timeout)? {
+        Some(status) => status,
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(WallrusError::Backend {
+                command: name,
+                message: format!("timed out after {:?}", timeout),
+            });
+        }
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if status.success() {
+        return Ok(stdout);
+    }
+    let stderr = stderr.trim();
+    Err(WallrusError::Backend {
+        command: name,
+        message: if stderr.is_empty() {
+            status.to_string()
+        } else {
+            stderr.to_string()
+        },
+    })
+}
+
+/// Waits for `child` to exit, giving up after `timeout`.
+pub fn wait(child: &mut Child, timeout: Duration) -> Result<Option<std::process::ExitStatus>> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        String::from_utf8_lossy(&output).into_owned()
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_carries_stderr() {
+        let output = run(Command::new("sh").args(["-c", "echo done"])).unwrap();
+        assert_eq!(output, "done\n");
+
+        let error =
+            run(Command::new("sh").args(["-c", "echo 'No such schema' >&2; exit 1"])).unwrap_err();
+        assert_eq!(error.to_string(), "sh failed: No such schema");
+    }
+
+    #[test]
+    fn test_slow_commands_are_killed() {
+        let start = Instant::now();
+        let error = run_with_timeout(Command::new("sleep").arg("5"), Duration::from_millis(100))
+            .unwrap_err();
+        assert!(matches!(error, WallrusError::Backend { .. }));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -1,5 +1,9 @@
 use chrono::prelude::Utc;
-use std::path::Path;
+use image::DynamicImage;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{Result, WallrusError};
 
 /// Generates a unique filename based on the current timestamp.
 /// This is useful for saving files that should not overwrite each other.
@@ -23,6 +27,80 @@ pub fn is_valid_file(path: &str) -> bool {
     Path::new(path).is_file()
 }
 
+/// Directory for persistent wallrus data such as the library index.
+pub fn data_dir() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("wallrus"))
+        .ok_or_else(|| WallrusError::Config("Could not determine data directory".into()))
+}
+
+/// Directory for state that may be lost without harm, such as the slideshow position.
+/// Falls back to the data directory on platforms without a state directory.
+pub fn state_dir() -> Result<PathBuf> {
+    match dirs::state_dir() {
+        Some(dir) => Ok(dir.join("wallrus")),
+        None => data_dir(),
+    }
+}
+
+/// Directory for sockets: `$XDG_RUNTIME_DIR`, falling back to the state directory where
+/// there is no runtime directory.
+pub fn runtime_dir() -> Result<PathBuf> {
+    match dirs::runtime_dir() {
+        Some(dir) => Ok(dir),
+        None => state_dir(),
+    }
+}
+
+/// Prepares `path` for binding a Unix socket: creates its directory and removes a socket left
+/// behind by a process that is gone. Returns false if a live process is still listening on it,
+/// and fails if the path belongs to another user.
+#[cfg(unix)]
+pub fn claim_socket(path: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        // SAFETY: geteuid has no preconditions and cannot fail.
+        if metadata.uid() != unsafe { libc::geteuid() } {
+            return Err(WallrusError::Config(format!(
+                "Socket {:?} belongs to another user, refusing to use it",
+                path
+            )));
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Ok(false);
+        }
+        fs::remove_file(path)?;
+    }
+    Ok(true)
+}
+
+/// Replaces the contents of `path` through a temporary file next to it, so a crash leaves
+/// either the old or the new contents rather than a truncated file.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", std::process::id()));
+    fs::write(&temp, data)?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Decodes an image, detecting its format from the file contents rather than the extension.
+pub fn open_image(path: &Path) -> Result<DynamicImage> {
+    image::io::Reader::open(path)?
+        .with_guessed_format()?
+        .decode()
+        .map_err(|e| {
+            WallrusError::ImageProcessing(format!("Failed to load image {:?}: {}", path, e))
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
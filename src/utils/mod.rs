@@ -1,6 +1,17 @@
+mod command;
 mod file;
+mod scan;
 mod string;
 
-pub use file::{generate_unique_filename, is_valid_file};
+pub use command::run;
+#[cfg(target_os = "linux")]
+pub use command::wait;
+#[cfg(unix)]
+pub use file::claim_socket;
+pub use file::{
+    data_dir, generate_unique_filename, is_valid_file, open_image, runtime_dir, state_dir,
+    write_atomic,
+};
+pub use scan::{scan_images, ScanOptions};
 #[allow(unused_imports)]
 pub use string::str_option_to_slice;
@@ -0,0 +1,260 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use image::ImageFormat;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{Result, WallrusError};
+
+/// Number of leading bytes read when sniffing the format of a file without a known extension.
+const SNIFF_LEN: usize = 32;
+
+/// Controls how image source directories are walked.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Descend into subdirectories.
+    pub recursive: bool,
+    /// Only keep images whose path relative to the source matches one of these globs.
+    pub include: Vec<String>,
+    /// Drop images whose path relative to the source matches one of these globs.
+    pub exclude: Vec<String>,
+}
+
+/// Collects every decodable image in `sources`, sorted by path.
+///
+/// Hidden files and directories are skipped, as are broken symlinks. A source may also be a
+/// single image file. Subdirectories that cannot be read (or vanish during the scan) are
+/// skipped with a warning; only an unreadable source is an error.
+pub fn scan_images(sources: &[PathBuf], options: &ScanOptions) -> Result<Vec<PathBuf>> {
+    let include = build_globset(&options.include)?;
+    let exclude = build_globset(&options.exclude)?;
+
+    let mut images = Vec::new();
+    let mut visited = HashSet::new();
+
+    for source in sources {
+        if source.is_file() {
+            if is_image(source) {
+                images.push(source.clone());
+            }
+            continue;
+        }
+        if !source.is_dir() {
+            return Err(WallrusError::Config(format!(
+                "Image source does not exist: {:?}",
+                source
+            )));
+        }
+
+        let mut pending = vec![source.clone()];
+        while let Some(dir) = pending.pop() {
+            let listing = dir
+                .canonicalize()
+                .and_then(|real| Ok((real, fs::read_dir(&dir)?)));
+            let (real, entries) = match listing {
+                Ok(listing) => listing,
+                Err(e) if dir != *source => {
+                    eprintln!("Warning: skipping {:?}: {}", dir, e);
+                    continue;
+                }
+                Err(e) => {
+                    return Err(WallrusError::Config(format!(
+                        "Cannot read image source {:?}: {}",
+                        source, e
+                    )))
+                }
+            };
+            // Guards against symlink loops and overlapping sources.
+            if !visited.insert(real) {
+                continue;
+            }
+
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if is_hidden(&path) {
+                    continue;
+                }
+
+                // `metadata` follows symlinks, so broken links fail here and are skipped.
+                let Ok(metadata) = fs::metadata(&path) else {
+                    continue;
+                };
+
+                if metadata.is_dir() {
+                    if options.recursive {
+                        pending.push(path);
+                    }
+                    continue;
+                }
+
+                let relative = path.strip_prefix(source).unwrap_or(&path);
+                if include.as_ref().is_some_and(|set| !set.is_match(relative)) {
+                    continue;
+                }
+                if exclude.as_ref().is_some_and(|set| set.is_match(relative)) {
+                    continue;
+                }
+
+                if is_image(&path) {
+                    images.push(path);
+                }
+            }
+        }
+    }
+
+    images.sort();
+    images.dedup();
+    Ok(images)
+}
+
+/// Returns true if the image decoder can read this file, judged by its extension
+/// (case-insensitively) or, failing that, by its magic bytes.
+pub fn is_image(path: &Path) -> bool {
+    match ImageFormat::from_path(path) {
+        Ok(format) => is_decodable(format),
+        Err(_) => sniff_format(path).is_some_and(is_decodable),
+    }
+}
+
+fn is_decodable(format: ImageFormat) -> bool {
+    // AVIF decoding needs an optional `image` feature that we do not enable.
+    format.can_read() && format != ImageFormat::Avif
+}
+
+fn sniff_format(path: &Path) -> Option<ImageFormat> {
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)
+        .ok()?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut header)
+        .ok()?;
+    image::guess_format(&header).ok()
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| {
+            WallrusError::Config(format!("Invalid glob pattern '{}': {}", pattern, e))
+        })?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| WallrusError::Config(format!("Invalid glob patterns: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    fn touch(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_scan_is_case_insensitive_and_recursive() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        touch(&root.join("a.JPG"), b"");
+        touch(&root.join("b.jpeg"), b"");
+        touch(&root.join("2024/03/c.webp"), b"");
+        touch(&root.join("notes.txt"), b"");
+
+        let flat = scan_images(&[root.to_path_buf()], &ScanOptions::default()).unwrap();
+        assert_eq!(flat.len(), 2);
+
+        let options = ScanOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        let nested = scan_images(&[root.to_path_buf()], &options).unwrap();
+        assert_eq!(nested.len(), 3);
+        assert!(nested.contains(&root.join("2024/03/c.webp")));
+    }
+
+    #[test]
+    fn test_scan_skips_hidden_and_broken_links() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        touch(&root.join(".hidden.png"), b"");
+        touch(&root.join(".cache/thumb.png"), b"");
+        touch(&root.join("visible.png"), b"");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.join("missing.png"), root.join("broken.png")).unwrap();
+
+        let options = ScanOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        let images = scan_images(&[root.to_path_buf()], &options).unwrap();
+        assert_eq!(images, vec![root.join("visible.png")]);
+    }
+
+    #[test]
+    fn test_scan_include_exclude_globs() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        touch(&root.join("2023/a.png"), b"");
+        touch(&root.join("2024/b.png"), b"");
+        touch(&root.join("2024/screenshots/c.png"), b"");
+
+        let options = ScanOptions {
+            recursive: true,
+            include: vec!["2024/**".into()],
+            exclude: vec!["**/screenshots/**".into()],
+        };
+        let images = scan_images(&[root.to_path_buf()], &options).unwrap();
+        assert_eq!(images, vec![root.join("2024/b.png")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_skips_unreadable_subdirectories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        touch(&root.join("a.png"), b"");
+        touch(&root.join("lost+found/b.png"), b"");
+        let locked = root.join("lost+found");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let options = ScanOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        let images = scan_images(&[root.to_path_buf()], &options);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        // Root reads the directory anyway; anyone else gets the readable image only.
+        assert!(images.unwrap().contains(&root.join("a.png")));
+    }
+
+    #[test]
+    fn test_is_image_sniffs_magic_bytes() {
+        let dir = tempdir().unwrap();
+        let png = dir.path().join("download");
+        let text = dir.path().join("README");
+        touch(&png, PNG_MAGIC);
+        touch(&text, b"hello world");
+
+        assert!(is_image(&png));
+        assert!(!is_image(&text));
+    }
+}